@@ -0,0 +1,122 @@
+#![feature(test)]
+
+extern crate test;
+
+mod common;
+
+use common::compile_block;
+use howl::{
+    std::block::call_block,
+    vm::{
+        bytecode::{send_cached, send_message},
+        cache::InlineCache,
+        runtime::Runtime,
+        value::Value,
+    },
+};
+use test::Bencher;
+
+#[bench]
+fn sum_loop(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(
+        "bench = [ s = 0; 1 to: 1000 do: [ i | s = s + i; ]; s; ];",
+        &mut rt,
+    );
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+#[bench]
+fn straight_line_sends(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(
+        "bench = [ a = 1; b = a + 2; c = b * 3; d = c - a; e = d max: b; f = e min: c; f; ];",
+        &mut rt,
+    );
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+/// Literal Int operands only, so every `+` and `<` takes the `AddInt`/`LessInt` fast path.
+#[bench]
+fn int_arithmetic(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(
+        "bench = [ 1 + 2; 3 + 4; 5 < 6; 7 + 8; 9 < 10; 11 + 12; 13 + 14; 15 < 16; ];",
+        &mut rt,
+    );
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+#[bench]
+fn unary_sends(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(
+        "bench = [ 1000 timesRepeat: [ x = 3 yourself; x = x negated; x = x abs; ]; ];",
+        &mut rt,
+    );
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+/// One call site seeing four receiver types, the most an inline cache holds.
+#[bench]
+fn polymorphic_sends(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(
+        r#"
+        receivers = "a,b,c,d" split: ",";
+        receivers at: 1 put: 1;
+        receivers at: 2 put: 1.5;
+        receivers at: 3 put: $a;
+        receivers at: 4 put: Nil;
+        bench = [ 250 timesRepeat: [ receivers do: [ r | s = r isNil; ]; ]; ];
+        "#,
+        &mut rt,
+    );
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+/// How many sends each of the benches below makes per iteration.
+const SENDS: usize = 1000;
+
+/// `3 yourself`, whose handler Int inherits from Object, looked up afresh every time.
+#[bench]
+fn send_uncached(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let id = rt.globals.idents.add("yourself");
+    b.iter(|| {
+        for _ in 0..SENDS {
+            rt.push_stack(Value::from_int(3));
+            send_message(&mut rt, id, 0);
+        }
+    });
+}
+
+/// The same send through an inline cache that always hits.
+#[bench]
+fn send_cached_hit(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let id = rt.globals.idents.add("yourself");
+    let cache = InlineCache::alloc(&mut rt.heap);
+    b.iter(|| {
+        for _ in 0..SENDS {
+            rt.push_stack(Value::from_int(3));
+            send_cached(&mut rt, id, 0, cache);
+        }
+    });
+}
+
+/// The same send through an inline cache invalidated before every send, as redefining a
+/// handler would.
+#[bench]
+fn send_cached_invalidated(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let id = rt.globals.idents.add("yourself");
+    let cache = InlineCache::alloc(&mut rt.heap);
+    b.iter(|| {
+        for _ in 0..SENDS {
+            rt.globals.handler_epoch += 1;
+            rt.push_stack(Value::from_int(3));
+            send_cached(&mut rt, id, 0, cache);
+        }
+    });
+}
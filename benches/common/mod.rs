@@ -0,0 +1,9 @@
+use howl::vm::{runtime::Runtime, value::Value};
+
+/// Runs `src`, which must bind a block to `bench`, and answers that block.
+pub fn compile_block(src: &str, rt: &mut Runtime) -> *const u8 {
+    howl::eval(src, rt);
+    let id = rt.globals.idents.find("bench").unwrap();
+    let block = rt.globals.vars.get(&Value::from_uint(id)).unwrap();
+    block.as_ptr() as *const u8
+}
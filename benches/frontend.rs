@@ -0,0 +1,71 @@
+#![feature(test)]
+
+extern crate test;
+
+use howl::{
+    IdentArena,
+    compiler::compile_stmt,
+    parser::howl_parser,
+    vm::{
+        bytecode::Chunk,
+        runtime::{Heap, Runtime},
+    },
+};
+use test::Bencher;
+
+/// Covers every kind of statement and expression the grammar has.
+const SNIPPET: &str = r#"
+count = 0;
+limit = 1000;
+total = count + limit;
+big = 123456789012345678901234567890;
+ratio = 3.25;
+letter = $q;
+greeting = "Hello, {letter} world \{escaped\}";
+hex = 0xFF;
+Point = Object named: "Point";
+Point instanceMessage: "norm" handler: [ p | 1; ];
+square = [ n | n * n; ];
+sum = square value: 12;
+1 to: 10 do: [ i | count = count + i; ];
+flag = count < limit;
+flag ifTrue: [ "small" display; ] ifFalse: [ "large" display; ];
+// a comment between statements
+"done" >>;
+"#;
+
+fn large_source() -> String {
+    SNIPPET.repeat(200)
+}
+
+#[bench]
+fn parse_large_source(b: &mut Bencher) {
+    let src = large_source();
+    let mut arena = IdentArena::default();
+    b.bytes = src.len() as u64;
+    b.iter(|| howl_parser::statements(&src, &mut arena).unwrap());
+}
+
+/// `compile_stmt` consumes its statement, so this includes cloning the parsed source.
+#[bench]
+fn compile_large_source(b: &mut Bencher) {
+    let src = large_source();
+    let mut rt = Runtime::default();
+    let stmts = howl::parse(&src, &mut rt).unwrap();
+    b.iter(|| {
+        let mut heap = Heap::new_with_capacity(16_000_000);
+        let mut chunk = Chunk::default();
+        for stmt in stmts.clone() {
+            compile_stmt(stmt, &mut chunk, &mut heap);
+        }
+        (chunk, heap)
+    });
+}
+
+#[bench]
+fn clone_large_source(b: &mut Bencher) {
+    let src = large_source();
+    let mut rt = Runtime::default();
+    let stmts = howl::parse(&src, &mut rt).unwrap();
+    b.iter(|| stmts.clone());
+}
@@ -0,0 +1,36 @@
+#![feature(test)]
+
+extern crate test;
+
+use howl::vm::{runtime::Heap, value::TypeId};
+use test::Bencher;
+
+const ALLOCATIONS: u64 = 10_000;
+
+/// Bump allocates `ALLOCATIONS` objects of `size` bytes into a heap made for them, including
+/// the cost of creating the heap.
+fn alloc_objects(b: &mut Bencher, size: u64) {
+    b.bytes = ALLOCATIONS * size;
+    b.iter(|| {
+        let mut heap = Heap::new_with_capacity(ALLOCATIONS * (size + 64));
+        for _ in 0..ALLOCATIONS {
+            test::black_box(heap.alloc(size, TypeId::Array));
+        }
+        heap
+    });
+}
+
+#[bench]
+fn alloc_small(b: &mut Bencher) {
+    alloc_objects(b, 16);
+}
+
+#[bench]
+fn alloc_medium(b: &mut Bencher) {
+    alloc_objects(b, 256);
+}
+
+#[bench]
+fn alloc_large(b: &mut Bencher) {
+    alloc_objects(b, 4_096);
+}
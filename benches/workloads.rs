@@ -0,0 +1,179 @@
+#![feature(test)]
+
+//! Classic interpreter workloads written in Howl.
+//!
+//! Howl only has global variables, so recursive handlers keep their per-call state in the
+//! receiver (which `super` answers) and accumulate results in globals. Workloads that allocate
+//! build a fresh runtime every iteration, since the heap is never collected; `runtime_new`
+//! measures that overhead on its own.
+
+extern crate test;
+
+mod common;
+
+use common::compile_block;
+use howl::{std::block::call_block, vm::runtime::Runtime};
+use test::Bencher;
+
+const FIB: &str = r#"
+Int instanceMessage: "fibInto" handler: [ n |
+    small = n < 2;
+    small ifTrue: [ fib = fib + n; ] ifFalse: [
+        a = n - 1;
+        a fibInto;
+        m = super;
+        b = m - 2;
+        b fibInto;
+    ];
+];
+bench = [ fib = 0; 20 fibInto; fib; ];
+"#;
+
+/// Nodes are `#(left right depth)` Arrays; the depth is kept in the node so that it survives
+/// the recursive calls.
+const BINARY_TREES: &str = r#"
+Array instanceMessage: "fill:" handler: [ node, depth |
+    node at: 3 put: depth;
+    leaf = depth < 1;
+    leaf ifFalse: [
+        child = "l,r,d" split: ",";
+        node at: 1 put: child;
+        d = depth - 1;
+        child fill: d;
+        node = super;
+        child = "l,r,d" split: ",";
+        node at: 2 put: child;
+        d = node at: 3;
+        d = d - 1;
+        child fill: d;
+    ];
+];
+Array instanceMessage: "check" handler: [ node |
+    nodes = nodes + 1;
+    depth = node at: 3;
+    leaf = depth < 1;
+    leaf ifFalse: [
+        child = node at: 1;
+        child check;
+        node = super;
+        child = node at: 2;
+        child check;
+    ];
+];
+tree = "l,r,d" split: ",";
+tree fill: 10;
+nodes = 0;
+tree check;
+"#;
+
+/// A planet orbiting a sun for one period, bodies being `#(x y vx vy mass)` Arrays.
+const NBODY: &str = r#"
+sun = "x,y,vx,vy,m" split: ",";
+sun at: 1 put: 0.0;
+sun at: 2 put: 0.0;
+sun at: 3 put: 0.0;
+sun at: 4 put: 0.0;
+sun at: 5 put: 39.47841760435743;
+planet = "x,y,vx,vy,m" split: ",";
+planet at: 1 put: 1.0;
+planet at: 2 put: 0.0;
+planet at: 3 put: 0.0;
+planet at: 4 put: 6.283185307179586;
+planet at: 5 put: 0.0001;
+dt = 0.001;
+advance = [
+    x1 = sun at: 1;
+    x2 = planet at: 1;
+    dx = x1 - x2;
+    y1 = sun at: 2;
+    y2 = planet at: 2;
+    dy = y1 - y2;
+    dx2 = dx * dx;
+    dy2 = dy * dy;
+    d2 = dx2 + dy2;
+    dist = d2 sqrt;
+    d3 = d2 * dist;
+    mag = dt / d3;
+    m1 = sun at: 5;
+    m2 = planet at: 5;
+    f1 = m2 * mag;
+    f2 = m1 * mag;
+    v = sun at: 3;
+    dv = dx * f1;
+    v = v - dv;
+    sun at: 3 put: v;
+    v = sun at: 4;
+    dv = dy * f1;
+    v = v - dv;
+    sun at: 4 put: v;
+    v = planet at: 3;
+    dv = dx * f2;
+    v = v + dv;
+    planet at: 3 put: v;
+    v = planet at: 4;
+    dv = dy * f2;
+    v = v + dv;
+    planet at: 4 put: v;
+    body = sun;
+    move value;
+    body = planet;
+    move value;
+];
+move = [
+    p = body at: 1;
+    v = body at: 3;
+    dp = v * dt;
+    p = p + dp;
+    body at: 1 put: p;
+    p = body at: 2;
+    v = body at: 4;
+    dp = v * dt;
+    p = p + dp;
+    body at: 2 put: p;
+];
+bench = [ 1000 timesRepeat: advance; ];
+"#;
+
+const STRING_BUILDING: &str = r#"
+s = "";
+1 to: 1000 do: [ i | s = "{s}{i},"; ];
+t = "";
+1000 timesRepeat: [ t = t , "x"; ];
+"#;
+
+#[bench]
+fn runtime_new(b: &mut Bencher) {
+    b.iter(Runtime::default);
+}
+
+#[bench]
+fn fib(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(FIB, &mut rt);
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+#[bench]
+fn binary_trees(b: &mut Bencher) {
+    b.iter(|| {
+        let mut rt = Runtime::default();
+        howl::eval(BINARY_TREES, &mut rt);
+        rt
+    });
+}
+
+#[bench]
+fn nbody(b: &mut Bencher) {
+    let mut rt = Runtime::default();
+    let block = compile_block(NBODY, &mut rt);
+    b.iter(|| unsafe { call_block(block, &mut rt, &[]) });
+}
+
+#[bench]
+fn string_building(b: &mut Bencher) {
+    b.iter(|| {
+        let mut rt = Runtime::default();
+        howl::eval(STRING_BUILDING, &mut rt);
+        rt
+    });
+}
@@ -1,13 +1,16 @@
-use howl::vm::runtime::Runtime;
+use howl::vm::runtime::{Config, Runtime};
 
 fn main() {
-    let mut rt = Runtime::default();
+    let mut rt = Runtime::new(Config {
+        trace: true,
+        ..Config::default()
+    });
     let file = include_str!("test.howl");
     let syntax = howl::parse(file, &mut rt).unwrap();
     // println!("{:#?}", syntax);
     howl::run(syntax, &mut rt);
 
-    println!("\nBytecode: {:#X?}", &rt.code);
+    println!("\nBytecode: {:#X?}", rt.code);
     println!("\nGlobals: {:#X?}", rt.globals.vars);
     println!("\nIdents: {:#X?}", rt.globals.idents);
 }
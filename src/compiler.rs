@@ -1,28 +1,35 @@
-use std::ptr::copy_nonoverlapping;
-
 use crate::{
-    parser::{Execution, Expr, Stmt},
+    IdentArena,
+    parser::{Execution, Expr, Literal, Segment, Stmt},
     vm::{
-        bytecode::OpCode,
+        bytecode::{Chunk, OpCode},
+        cache::InlineCache,
         runtime::Heap,
-        value::{TypeId, Value},
+        value::Value,
     },
 };
 
-pub fn compile_stmt(stmt: Stmt, code: &mut Vec<OpCode>, heap: &mut Heap) {
+pub fn compile_stmt(stmt: Stmt, code: &mut Chunk, heap: &mut Heap) {
+    compile_stmt_in(stmt, code, heap, false);
+}
+
+/// `tail` is set for the last statement of a block, whose send then answers the block's value
+/// and can run in place of the block, see `OpCode::TailSend`.
+fn compile_stmt_in(stmt: Stmt, code: &mut Chunk, heap: &mut Heap, tail: bool) {
+    code.mark_source(stmt.span().start());
     match stmt {
-        Stmt::Exe(e) => compile_execution(e, code, heap),
-        Stmt::Assignment { dst, rhs } => {
+        Stmt::Exe(e, _) => compile_execution(e, code, heap, tail),
+        Stmt::Assignment { dst, rhs, .. } => {
             // compile_execution(lhs, rt);
-            compile_execution(rhs, code, heap);
+            compile_execution(rhs, code, heap, false);
             // TODO!!!!!!!
             // match on lhs if singular we can avoid execution
-            code.push(OpCode::SetGlobal(dst.id));
+            code.emit(OpCode::SetGlobal(dst.id));
         }
     };
 }
 
-pub fn compile_execution(exe: Execution, code: &mut Vec<OpCode>, heap: &mut Heap) {
+pub fn compile_execution(exe: Execution, code: &mut Chunk, heap: &mut Heap, tail: bool) {
     match exe {
         Execution::Single(e) => compile_expr(e, code, heap),
         Execution::Called(instance, message, args) => {
@@ -30,7 +37,10 @@ pub fn compile_execution(exe: Execution, code: &mut Vec<OpCode>, heap: &mut Heap
                 Expr::Ident(i) => i,
                 Expr::Lit(_) => panic!("Cannot have literal as message"),
                 Expr::Block(_) => panic!("Cannot have block as message"),
+                Expr::Interpolation(_) => panic!("Cannot have string as message"),
+                Expr::Super => panic!("Cannot have super as message"),
             };
+            let is_super = matches!(instance, Expr::Super);
             compile_expr(instance, code, heap);
 
             let arg_count = args.len() as u64;
@@ -38,37 +48,60 @@ pub fn compile_execution(exe: Execution, code: &mut Vec<OpCode>, heap: &mut Heap
                 compile_expr(arg, code, heap);
             }
 
-            code.push(OpCode::SendMessage {
-                id: message.id,
-                arg_count,
+            let id = message.id;
+            if is_super {
+                code.emit(OpCode::SuperSend { id, arg_count });
+                return;
+            }
+            let cache = InlineCache::alloc(heap);
+            let cache = code.add_const(Value::from_ptr(cache.as_ptr() as u64));
+            code.emit(match (id, arg_count) {
+                (id, arg_count) if tail => OpCode::TailSend {
+                    id,
+                    arg_count,
+                    cache,
+                },
+                (IdentArena::ADD, 1) => OpCode::AddInt { cache },
+                (IdentArena::LESS, 1) => OpCode::LessInt { cache },
+                (id, 0) => OpCode::SendUnary0 { id, cache },
+                (id, 1) => OpCode::SendBinary { id, cache },
+                (id, arg_count) => OpCode::SendMessage {
+                    id,
+                    arg_count,
+                    cache,
+                },
             });
         }
     }
 }
 
-pub fn compile_expr(expr: Expr, code: &mut Vec<OpCode>, heap: &mut Heap) {
+pub fn compile_expr(expr: Expr, code: &mut Chunk, heap: &mut Heap) {
     match expr {
-        Expr::Lit(l) => code.push(OpCode::PushLit(Value::from_literal(l, heap))),
-        Expr::Ident(i) => code.push(OpCode::PushGlobal(i.id)),
+        Expr::Lit(Literal::Int(i)) => code.emit(OpCode::PushSmallInt(i)),
+        Expr::Lit(l) => code.push_const(Value::from_literal(l, heap)),
+        Expr::Ident(i) => code.emit(OpCode::PushGlobal(i.id)),
+        Expr::Super => code.emit(OpCode::PushSelf),
+        Expr::Interpolation(segments) => {
+            let count = segments.len() as u64;
+            for segment in segments {
+                match segment {
+                    Segment::Text(s) => code.push_const(Value::from_str(&s, heap)),
+                    Segment::Exe(e) => compile_execution(e, code, heap, false),
+                }
+            }
+            code.emit(OpCode::BuildString(count));
+        }
         Expr::Block(b) => {
-            let mut ops = Vec::new();
-            for stmt in b {
-                compile_stmt(stmt, &mut ops, heap);
+            let mut block = Chunk::default();
+            // Arguments are pushed in order, so bind them back to front
+            for param in b.params.iter().rev() {
+                block.emit(OpCode::SetGlobal(param.id));
             }
-
-            let header_size = 16;
-            let ptr = heap
-                .alloc(
-                    (header_size + ops.len() * size_of::<OpCode>()) as u64,
-                    TypeId::CompiledBytecode,
-                )
-                .unwrap();
-            unsafe {
-                ptr.cast().write(ops.len() as u64);
-                let slice_start = ptr.add(header_size).cast::<OpCode>().as_ptr();
-                copy_nonoverlapping(ops.as_ptr(), slice_start, ops.len());
+            let last = b.body.len().saturating_sub(1);
+            for (i, stmt) in b.body.into_iter().enumerate() {
+                compile_stmt_in(stmt, &mut block, heap, i == last);
             }
-            code.push(OpCode::PushLit(Value::from_ptr(ptr.as_ptr() as u64)));
+            code.push_const(block.alloc_block(b.params.len() as u64, heap));
         }
     }
 }
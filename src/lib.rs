@@ -1,12 +1,16 @@
 #![feature(maybe_uninit_array_assume_init)]
 #![feature(allocator_api)]
-#![feature(ptr_as_ref_unchecked)]
 
 use crate::{
     parser::{Stmt, howl_parser},
-    vm::runtime::Runtime,
+    std::{
+        error::{RaisedError, UnhandledError},
+        test_case::AssertionFailure,
+    },
+    vm::{bytecode::Chunk, runtime::Runtime},
 };
-use ::std::{collections::HashMap, ops::Range, rc::Rc};
+use ::std::{any::Any, collections::HashMap, ops::Range, rc::Rc};
+use annotate_snippets::{AnnotationKind, Level, Renderer, Snippet};
 use peg::{error::ParseError, str::LineCol};
 
 pub mod compiler;
@@ -17,13 +21,41 @@ pub mod vm;
 #[derive(PartialEq, Eq, Debug, Clone, Hash)]
 pub struct Span(Range<usize>);
 
-#[derive(Default, Debug)]
+impl Span {
+    pub fn start(&self) -> usize {
+        self.0.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.0.end
+    }
+}
+
+#[derive(Debug)]
 pub struct IdentArena {
     map: HashMap<Rc<str>, u64>,
     vec: Vec<Rc<str>>,
 }
 
+/// Selectors the compiler emits specialised instructions for are interned first, so their ids
+/// are known without an arena.
+impl Default for IdentArena {
+    fn default() -> Self {
+        let mut arena = Self {
+            map: HashMap::new(),
+            vec: Vec::new(),
+        };
+        let add = arena.add("+");
+        let less = arena.add("<");
+        debug_assert_eq!((add, less), (Self::ADD, Self::LESS));
+        arena
+    }
+}
+
 impl IdentArena {
+    pub const ADD: u64 = 0;
+    pub const LESS: u64 = 1;
+
     pub fn add(&mut self, s: &str) -> u64 {
         let rc_s = Rc::from(s);
 
@@ -37,6 +69,11 @@ impl IdentArena {
         id
     }
 
+    /// The id of an already interned identifier.
+    pub fn find(&self, s: &str) -> Option<u64> {
+        self.map.get(s).copied()
+    }
+
     pub fn get(&self, id: u64) -> Option<Rc<str>> {
         self.vec.get(id as usize).map(Rc::clone)
     }
@@ -51,6 +88,51 @@ pub fn parse(s: &str, rt: &mut Runtime) -> Result<Vec<Stmt>, ParseError<LineCol>
     howl_parser::statements(s, &mut rt.globals.idents)
 }
 
+/// Renders a parse error as a diagnostic quoting the offending part of `source`, which was read
+/// from `path`.
+pub fn parse_diagnostic(source: &str, path: &str, error: &ParseError<LineCol>) -> String {
+    let start = error.location.offset;
+    let end = source[start..]
+        .chars()
+        .next()
+        .map_or(start, |c| start + c.len_utf8());
+    let title = format!("expected {}", error.expected);
+    let report = &[Level::ERROR.primary_title(title).element(
+        Snippet::source(source)
+            .path(path)
+            .annotation(AnnotationKind::Primary.span(start..end)),
+    )];
+    Renderer::plain().render(report)
+}
+
+/// The 1-based line number containing `offset`, along with that line's trimmed text.
+pub fn source_line(source: &str, offset: usize) -> (usize, &str) {
+    let offset = offset.min(source.len());
+    let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let line = source[..offset].matches('\n').count() + 1;
+    (line, source[start..end].trim())
+}
+
+/// The message a runtime error was raised with, given the payload it unwound with.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(unhandled) = payload.downcast_ref::<UnhandledError>() {
+        unhandled.message.clone()
+    } else if let Some(raised) = payload.downcast_ref::<RaisedError>() {
+        raised.message.clone()
+    } else if let Some(failure) = payload.downcast_ref::<AssertionFailure>() {
+        failure.0.clone()
+    } else if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown error".to_string()
+    }
+}
+
 pub fn run(stmts: Vec<Stmt>, rt: &mut Runtime) {
     for stmt in stmts {
         compiler::compile_stmt(stmt, &mut rt.code, &mut rt.heap);
@@ -58,6 +140,17 @@ pub fn run(stmts: Vec<Stmt>, rt: &mut Runtime) {
     vm::bytecode::flush_runtime(rt);
 }
 
+/// Compiles and optimizes `stmts` into a chunk of their own without running them, e.g. for
+/// disassembly.
+pub fn compile(stmts: Vec<Stmt>, rt: &mut Runtime) -> Chunk {
+    let mut chunk = Chunk::default();
+    for stmt in stmts {
+        compiler::compile_stmt(stmt, &mut chunk, &mut rt.heap);
+    }
+    vm::optimizer::optimize(&mut chunk, rt);
+    chunk
+}
+
 pub fn eval(s: &str, rt: &mut Runtime) {
     let stmts = howl_parser::statements(s, &mut rt.globals.idents).unwrap();
     for stmt in stmts {
@@ -0,0 +1,202 @@
+use howl::{
+    std::{
+        object::print_string,
+        test_case::{TestOutcome, TestResult, catch_error, format_results, run_tests, summarize},
+    },
+    vm::{
+        disassembler::disassemble,
+        optimizer::OptLevel,
+        runtime::{Config, Runtime},
+    },
+};
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+    process,
+};
+
+const USAGE: &str = "usage: howl [--disassemble] [-O0|-O1|-O2] [--no-tail-calls] [FILE]
+       howl test [-O0|-O1|-O2] [--no-tail-calls] FILE...
+
+Runs FILE, or starts a REPL when no file is given. `howl test` runs the tests of every
+TestCase type defined in each FILE.
+
+options:
+  -d, --disassemble  print FILE's bytecode instead of running it
+  -O0                don't optimize (the default)
+  -O1                only apply rewrites that never change behaviour
+  -O2, -O            also fold sends to literals
+  --no-tail-calls    keep a frame for every handler instead of reusing the frames of
+                     handlers ending in a send";
+
+const REPL_HELP: &str = ":dis CODE  print the bytecode CODE compiles to
+:help      show this message
+:quit      leave the REPL";
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let testing = args.next_if_eq("test").is_some();
+    let mut disassemble_only = false;
+    let mut config = Config::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-d" | "--disassemble" if !testing => disassemble_only = true,
+            "-O0" => config.opt_level = OptLevel::None,
+            "-O1" => config.opt_level = OptLevel::Peephole,
+            "-O2" | "-O" => config.opt_level = OptLevel::Fold,
+            "--no-tail-calls" => config.no_tail_calls = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ if (testing || paths.is_empty()) && !arg.starts_with('-') => paths.push(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                process::exit(2);
+            }
+        }
+    }
+
+    if testing {
+        if paths.is_empty() {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+        test_files(&paths, config);
+        return;
+    }
+
+    let mut rt = Runtime::new(config);
+    match paths.pop() {
+        Some(path) => run_file(&mut rt, &path, disassemble_only),
+        None if disassemble_only => {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+        None => repl(&mut rt),
+    }
+}
+
+fn read_source(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("error: couldn't read {path}: {e}");
+        process::exit(1);
+    })
+}
+
+fn run_file(rt: &mut Runtime, path: &str, disassemble_only: bool) {
+    let source = read_source(path);
+    let stmts = howl::parse(&source, rt).unwrap_or_else(|e| {
+        eprintln!("{}", howl::parse_diagnostic(&source, path, &e));
+        process::exit(1);
+    });
+    if disassemble_only {
+        let chunk = howl::compile(stmts, rt);
+        print!("{}", disassemble(rt, &chunk, Some(&source)));
+    } else {
+        // Errors are reported once they reach the top rather than where they were raised, since
+        // a handler may take them on the way
+        panic::set_hook(Box::new(|_| {}));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| howl::run(stmts, rt)));
+        if let Err(payload) = result {
+            rt.out.flush().expect("Failed to flush output");
+            eprintln!("error: {}", howl::panic_message(&*payload));
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs each file in a runtime of its own followed by its tests, exiting with a failure status
+/// unless every test passed. A file that fails to load counts as a single error.
+fn test_files(paths: &[String], config: Config) {
+    // Errors are reported as test results rather than where they were raised
+    panic::set_hook(Box::new(|_| {}));
+    let mut results = Vec::new();
+    for path in paths {
+        let source = read_source(path);
+        let mut rt = Runtime::new(config.clone());
+        let loaded = match howl::parse(&source, &mut rt) {
+            Ok(stmts) => catch_error(&mut rt, |rt| howl::run(stmts, rt))
+                .map_err(|payload| howl::panic_message(&*payload)),
+            Err(e) => Err(howl::parse_diagnostic(&source, path, &e)),
+        };
+        let file_results = match loaded {
+            Ok(()) => run_tests(&mut rt),
+            Err(message) => vec![TestResult {
+                name: path.clone(),
+                outcome: TestOutcome::Errored(message),
+                source_offset: None,
+            }],
+        };
+        print!("{}", format_results(&file_results, &source, path));
+        results.extend(file_results);
+    }
+
+    println!("\n{}", summarize(&results));
+    if results.iter().any(|r| r.outcome != TestOutcome::Passed) {
+        process::exit(1);
+    }
+}
+
+fn repl(rt: &mut Runtime) {
+    // Errors are reported per line instead of ending the session
+    panic::set_hook(Box::new(|_| {}));
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("howl> ");
+        io::stdout().flush().expect("Failed to flush prompt");
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        let line = line.trim();
+        match line.split_once(' ').unwrap_or((line, "")) {
+            ("", _) => {}
+            (":quit" | ":q", _) => break,
+            (":help" | ":h", _) => println!("{REPL_HELP}"),
+            (":dis", code) => repl_disassemble(rt, code),
+            (command, _) if command.starts_with(':') => {
+                eprintln!("error: unknown command {command}, try :help")
+            }
+            _ => repl_eval(rt, line),
+        }
+    }
+}
+
+fn repl_disassemble(rt: &mut Runtime, code: &str) {
+    match howl::parse(code, rt) {
+        Ok(stmts) => {
+            let chunk = howl::compile(stmts, rt);
+            print!("{}", disassemble(rt, &chunk, Some(code)));
+        }
+        Err(e) => eprintln!("{}", howl::parse_diagnostic(code, "<repl>", &e)),
+    }
+}
+
+/// Runs `code`, printing the value of its last statement if it left one.
+fn repl_eval(rt: &mut Runtime, code: &str) {
+    let stmts = match howl::parse(code, rt) {
+        Ok(stmts) => stmts,
+        Err(e) => {
+            eprintln!("{}", howl::parse_diagnostic(code, "<repl>", &e));
+            return;
+        }
+    };
+    let checkpoint = rt.checkpoint();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let base = rt.stack.len();
+        howl::run(stmts, rt);
+        if rt.stack.len() > base {
+            let value = rt.pop_stack();
+            println!("{}", print_string(rt, value));
+        }
+    }));
+    if let Err(payload) = result {
+        rt.out.flush().expect("Failed to flush output");
+        eprintln!("error: {}", howl::panic_message(&*payload));
+    }
+    rt.restore(checkpoint);
+}
@@ -1,7 +1,8 @@
-use crate::IdentArena;
+use crate::{IdentArena, Span, vm::bigint::BigInt};
 
 pub enum Keyword {
     Type,
+    Super,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -11,8 +12,21 @@ pub struct Ident {
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    Assignment { dst: Ident, rhs: Execution },
-    Exe(Execution),
+    Assignment {
+        dst: Ident,
+        rhs: Execution,
+        span: Span,
+    },
+    Exe(Execution, Span),
+}
+
+impl Stmt {
+    /// Where the statement sits in the parsed source, excluding its `;`
+    pub fn span(&self) -> &Span {
+        match self {
+            Stmt::Assignment { span, .. } | Stmt::Exe(_, span) => span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,19 +38,47 @@ pub enum Execution {
 #[derive(Clone, Debug)]
 pub enum Expr {
     Ident(Ident),
-    Block(Vec<Stmt>),
+    Block(Block),
     Lit(Literal),
+    /// The running handler's receiver, with sends to it looked up from the holder's superclass
+    Super,
+    /// A string literal with `{...}` holes, e.g. `"Hello, {name}!"`
+    Interpolation(Vec<Segment>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Segment {
+    Text(String),
+    Exe(Execution),
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub params: Vec<Ident>,
+    pub body: Vec<Stmt>,
 }
 
 #[derive(Clone, Debug)]
 pub enum Literal {
     Int(i32),
+    LargeInt(BigInt),
     Float(f64),
     Bool(bool),
+    Char(char),
     String(String),
     Nil,
 }
 
+fn int_literal(negative: bool, digits: &str, radix: u32) -> Literal {
+    let n = BigInt::parse_radix(&digits.replace('_', ""), radix)
+        .expect("grammar only admits valid digits");
+    let n = if negative { n.negated() } else { n };
+    match n.to_i32() {
+        Some(i) => Literal::Int(i),
+        None => Literal::LargeInt(n),
+    }
+}
+
 peg::parser! {
     pub grammar howl_parser(arena: &mut IdentArena) for str {
         // Helpers
@@ -44,35 +86,85 @@ peg::parser! {
         rule comment() = "//" (!"\n" [_])* ("\n" / ![_])
         rule _() = quiet! { (wsp() / comment())* }
         rule digit() -> &'input str = quiet! { $[c if c.is_ascii_digit()] } / expected!("digit")
-        rule terminator() = ['(' | ')' | '[' | ']' | ',' | ':' | ';' | '|' | '"'] / wsp()
+        rule terminator() = ['(' | ')' | '[' | ']' | '{' | '}' | ',' | ':' | ';' | '|' | '"'] / wsp()
         rule eq() = quiet!{ "=" } / expected!("EQUAL")
 
         // Atoms
-        rule int_literal() -> i32 = n:$(digit()+) { ? n.parse().or(Err("i32")) }
-        rule float_literal() -> f64 = n:$(digit()+ "." digit()*) { ? n.parse().or(Err("f64")) }
+        rule hex_digit() = quiet! { ['0'..='9' | 'a'..='f' | 'A'..='F'] } / expected!("hexadecimal digit")
+        rule oct_digit() = quiet! { ['0'..='7'] } / expected!("octal digit")
+        rule bin_digit() = quiet! { ['0' | '1'] } / expected!("binary digit")
+        // Underscores may separate digits, but can't lead, trail or repeat
+        rule dec_digits() = digit() (quiet! { "_" }? digit())*
+        rule hex_digits() = hex_digit() (quiet! { "_" }? hex_digit())*
+        rule oct_digits() = oct_digit() (quiet! { "_" }? oct_digit())*
+        rule bin_digits() = bin_digit() (quiet! { "_" }? bin_digit())*
+        rule exponent() = quiet! { ['e' | 'E'] } ['+' | '-']? dec_digits()
+        rule end_of_number() = &terminator() / ![_] / expected!("end of numeric literal")
+        rule int_literal() -> Literal =
+            neg:"-"? "0x" d:$(hex_digits()) end_of_number() { int_literal(neg.is_some(), d, 16) } /
+            neg:"-"? "0o" d:$(oct_digits()) end_of_number() { int_literal(neg.is_some(), d, 8) } /
+            neg:"-"? "0b" d:$(bin_digits()) end_of_number() { int_literal(neg.is_some(), d, 2) } /
+            neg:"-"? d:$(dec_digits()) end_of_number() { int_literal(neg.is_some(), d, 10) }
+        rule float_literal() -> f64 =
+            n:$("-"? dec_digits() ("." dec_digits()? exponent()? / exponent())) end_of_number() { ?
+                let f: f64 = n.replace('_', "").parse().or(Err("float literal"))?;
+                if f.is_finite() { Ok(f) } else { Err("float literal within the range of a Float") }
+            }
+        rule char_literal() -> char = "$" c:[_] { c }
         rule bool_literal() -> bool = "True" { true } / "False" { false }
-        rule str_literal() -> String = "\"" s:("\\" c:['\"' | '\\' | 'n' | 'r' | 't'] { ?
+        rule str_char() -> char = "\\" c:['\"' | '\\' | '{' | '}' | 'n' | 'r' | 't'] { ?
             match c { 'n' => Ok('\n'), 'r' => Ok('\r'), 't' => Ok('\t'), '\\' => Ok('\\'), '"' => Ok('"'),
-                _ => Err("Invalid escape character") }} / c:[^ '\"' | '\\'] { c })* "\"" { s.into_iter().collect() }
-        rule keyword() -> Keyword = "type" &terminator() { Keyword::Type }
+                '{' => Ok('{'), '}' => Ok('}'), _ => Err("Invalid escape character") }} / c:[^ '\"' | '\\' | '{'] { c }
+        rule str_segment() -> Segment =
+            "{" _() e:execution() _() "}" { Segment::Exe(e) } / s:str_char()+ { Segment::Text(s.into_iter().collect()) }
+        // Plain strings stay literals; only strings with holes need building at runtime
+        rule str_literal() -> Expr = "\"" segments:str_segment()* "\"" {
+            match segments.as_slice() {
+                [] => Expr::Lit(Literal::String(String::new())),
+                [Segment::Text(s)] => Expr::Lit(Literal::String(s.clone())),
+                _ => Expr::Interpolation(segments),
+            }
+        }
+        rule keyword() -> Keyword = "type" &terminator() { Keyword::Type } / "super" &terminator() { Keyword::Super }
+        // Identifiers run up to a terminator, so selectors may use punctuation such as the `!` of
+        // `verifyEmail!:`, which by convention answers an error rather than signaling it
         rule identifier() -> Ident =
-            quiet!{ s:$(!keyword() (!terminator() [_])+) { Ident { id: arena.add(s) } } } / expected!("identifier")
-        rule block() -> Vec<Stmt> =
-            "[" s:statements() "]" { s }
+            quiet!{ s:$(!keyword() !("-"? digit()) (!terminator() [_])+) { Ident { id: arena.add(s) } } } /
+            expected!("identifier")
+        rule keyword_part() -> &'input str = $((!terminator() [_])+ ":")
+        rule params() -> Vec<Ident> = _() p:(identifier() ++ (_() "," _())) _() "|" { p }
+        rule block() -> Block =
+            "[" params:params()? body:statements() "]" { Block { params: params.unwrap_or_default(), body } }
+
+        // `,` and `|` are terminators everywhere else, but are also the concatenation and
+        // disjunction selectors
+        rule selector() -> Expr =
+            expression() / s:$("," / "|") { Expr::Ident(Ident { id: arena.add(s) }) }
 
         // Language constructs
         rule expression() -> Expr =
-            f:float_literal() { Expr::Lit(Literal::Float(f))} / i:int_literal() { Expr::Lit(Literal::Int(i))} /
-            b:bool_literal() { Expr::Lit(Literal::Bool(b))} / s:str_literal() { Expr::Lit(Literal::String(s.to_string()))} /
-            "Nil" { Expr::Lit(Literal::Nil) } / i:identifier() { Expr::Ident(i)} / b:block() { Expr::Block(b) }
+            f:float_literal() { Expr::Lit(Literal::Float(f))} / i:int_literal() { Expr::Lit(i) } /
+            b:bool_literal() { Expr::Lit(Literal::Bool(b))} / c:char_literal() { Expr::Lit(Literal::Char(c)) } / str_literal() /
+            "Nil" { Expr::Lit(Literal::Nil) } / "super" &terminator() { Expr::Super } / i:identifier() { Expr::Ident(i)} / b:block() { Expr::Block(b) }
         rule execution() -> Execution =
-            i:expression() wsp() m:expression() a:(wsp() e:expression() {e})* { Execution::Called(i, m, a) } /
+            i:expression() parts:(wsp() k:keyword_part() _() e:expression() { (k, e) })+ {
+                let selector: String = parts.iter().map(|(k, _)| *k).collect();
+                let args = parts.into_iter().map(|(_, e)| e).collect();
+                Execution::Called(i, Expr::Ident(Ident { id: arena.add(&selector) }), args)
+            } /
+            i:expression() wsp() m:selector() a:(wsp() e:expression() {e})* { Execution::Called(i, m, a) } /
             i:expression() { Execution::Single(i) }
         rule assignment() -> (Ident, Execution) = lhs:identifier() _() eq() _() rhs:execution() { (lhs, rhs) }
         rule stmt() -> Stmt =
-            stmt:(
-                a:assignment() { Stmt::Assignment { dst: a.0, rhs: a.1 } } / e:execution() { Stmt::Exe(e) }
-            ) _() ";" _() { stmt }
+            start:position!() stmt:(
+                a:assignment() { (Some(a.0), a.1) } / e:execution() { (None, e) }
+            ) end:position!() _() ";" _() {
+                let span = Span(start..end);
+                match stmt {
+                    (Some(dst), rhs) => Stmt::Assignment { dst, rhs, span },
+                    (None, e) => Stmt::Exe(e, span),
+                }
+            }
 
         // Top-level
         pub rule statements() -> Vec<Stmt> = _() stmts:stmt()* _() { stmts }
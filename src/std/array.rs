@@ -0,0 +1,131 @@
+use crate::{
+    std::{
+        block::{call_block, expect_block},
+        error::{expect_arg_count, raise},
+        object::print_string,
+    },
+    vm::{
+        runtime::{Heap, Runtime},
+        value::{TypeId, Value},
+    },
+};
+use std::{ptr::NonNull, slice};
+
+pub fn define_array(rt: &mut Runtime) {
+    let id = TypeId::Array;
+    rt.define_type(id);
+
+    define_array_size(rt, id);
+    define_array_is_empty(rt, id);
+    define_array_at(rt, id);
+    define_array_at_put(rt, id);
+    define_array_do(rt, id);
+    define_array_print_string(rt, id);
+}
+
+/// Arrays store their length in a 16-byte header followed by the elements.
+pub fn new_array(heap: &mut Heap, elements: &[Value]) -> Value {
+    let header_len = 16;
+    let ptr = heap.alloc((header_len + size_of_val(elements)) as u64, TypeId::Array);
+    unsafe {
+        ptr.cast::<u64>().write(elements.len() as u64);
+        ptr.add(header_len)
+            .cast::<Value>()
+            .copy_from_nonoverlapping(NonNull::from(elements).cast(), elements.len());
+    }
+    Value::from_ptr(ptr.as_ptr() as u64)
+}
+
+pub fn as_slice(v: Value) -> &'static mut [Value] {
+    let ptr = v.as_ptr() as *mut u8;
+    unsafe {
+        let len = ptr.cast::<u64>().read();
+        slice::from_raw_parts_mut(ptr.add(16).cast::<Value>(), len as usize)
+    }
+}
+
+/// Converts a 1-based Howl index into a slice index, raising `IndexOutOfBounds` when it is out of bounds.
+pub fn checked_index(index: Value, len: usize) -> usize {
+    if !index.is_int() {
+        raise(TypeId::ArgumentError, "Expected an Int index");
+    }
+    let i = index.as_int();
+    if i < 1 || i as usize > len {
+        raise(
+            TypeId::IndexOutOfBounds,
+            format!("Index {i} out of bounds for size {len}"),
+        );
+    }
+    i as usize - 1
+}
+
+fn define_array_size(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_slice(rt.pop_stack());
+        Some(Value::from_int(lhs.len() as i32))
+    }
+    rt.register_handler("size", handler, id);
+}
+
+fn define_array_is_empty(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_slice(rt.pop_stack());
+        Some(Value::from_bool(lhs.is_empty()))
+    }
+    rt.register_handler("isEmpty", handler, id);
+}
+
+fn define_array_at(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let index = rt.pop_stack();
+        let lhs = as_slice(rt.pop_stack());
+        Some(lhs[checked_index(index, lhs.len())])
+    }
+    rt.register_handler("at:", handler, id);
+}
+
+fn define_array_at_put(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [index, value] = rt.pop_stack_n::<2>();
+        let lhs = as_slice(rt.pop_stack());
+        lhs[checked_index(index, lhs.len())] = value;
+        None
+    }
+    rt.register_handler("at:put:", handler, id);
+}
+
+fn define_array_do(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = expect_block(rt.pop_stack());
+        let lhs = as_slice(rt.pop_stack());
+        for &element in lhs.iter() {
+            unsafe { call_block(block, rt, &[element]) };
+        }
+        None
+    }
+    rt.register_handler("do:", handler, id);
+}
+
+/// Answers e.g. `#(1 $a "b")`, printing each element in turn.
+/// Printing the elements recurses natively, so it counts against `max_depth` like a Block
+/// does, and an Array that contains itself overflows rather than crashing.
+fn define_array_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_slice(rt.pop_stack());
+        if rt.depth >= rt.config.max_depth {
+            rt.stack_overflow(format!("Arrays nested deeper than {}", rt.config.max_depth));
+        }
+        rt.depth += 1;
+        let elements: Vec<&str> = lhs.iter().map(|&e| print_string(rt, e)).collect();
+        rt.depth -= 1;
+        let s = format!("#({})", elements.join(" "));
+        Some(Value::from_str(&s, &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
@@ -1,51 +1,192 @@
-use crate::vm::{
-    bytecode::{OpCode, exe},
-    runtime::Runtime,
-    value::{TypeId, Value},
+use crate::{
+    std::error::{ensure, expect_arg_count, on_do, raise},
+    vm::{
+        bytecode::{block_lines, block_parts, exe, run_tail_call},
+        runtime::{Runtime, as_type_object},
+        value::{TypeId, Value},
+    },
 };
-use std::slice;
 
 pub fn define_block(rt: &mut Runtime) {
     let id = TypeId::CompiledBytecode;
     rt.define_type(id);
 
     define_block_run(rt, id);
+    define_block_run_with::<1>(rt, id);
+    define_block_run_with::<2>(rt, id);
+    define_block_param_count(rt, id);
     define_block_loop(rt, id);
+    define_block_on_do(rt, id);
+    define_block_ensure::<true>(rt, id);
+    define_block_ensure::<false>(rt, id);
 }
 
 fn define_block_run(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 0 {
-            panic!("Bad args!")
-        }
+        expect_arg_count(arg_count, 0);
         let ptr = rt.pop_stack().as_ptr() as *const u8;
-        unsafe { run_block(ptr, rt) };
-
-        None
+        unsafe { call_block_in_tail(ptr, rt, &[]) }
     }
     rt.register_handler("value", handler, id);
 }
 
+fn define_block_run_with<const N: usize>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const N: usize>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, N as u64);
+        let args = rt.pop_stack_n::<N>();
+        let ptr = rt.pop_stack().as_ptr() as *const u8;
+        unsafe { call_block_in_tail(ptr, rt, &args) }
+    }
+    let selector = match N {
+        1 => "value:",
+        2 => "value:value:",
+        _ => unreachable!(),
+    };
+    rt.register_handler(selector, handler::<N>, id);
+}
+
+fn define_block_param_count(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let ptr = rt.pop_stack().as_ptr() as *const u8;
+        Some(Value::from_int(unsafe { param_count(ptr) } as i32))
+    }
+    rt.register_handler("numArgs", handler, id);
+}
+
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by the compiler.
 pub unsafe fn run_block(ptr: *const u8, rt: &mut Runtime) {
-    let header_size = 16;
+    if rt.depth >= rt.config.max_depth {
+        rt.stack_overflow(format!("Blocks nested deeper than {}", rt.config.max_depth));
+    }
+    // Counts as an instruction, so that looping over an empty block still uses fuel
+    rt.budget.tick();
+    let (_, consts, code) = unsafe { block_parts(ptr) };
+    rt.depth += 1;
+    exe(code, consts, unsafe { block_lines(ptr) }, rt);
+    rt.depth -= 1;
+}
 
-    let ops = unsafe {
-        let ops_len = ptr.cast::<u64>().read();
-        let slice_start = ptr.add(header_size).cast::<OpCode>();
-        slice::from_raw_parts(slice_start, ops_len as usize)
-    };
-    exe(ops.iter().copied(), rt);
+/// Evaluates a Block argument for control flow, answering Nil when it leaves no value. Blocks
+/// may leave off trailing parameters they don't need, so `args` is cut to fit.
+pub fn evaluate(rt: &mut Runtime, block: Value, args: &[Value]) -> Value {
+    let value = evaluate_in_tail(rt, block, args);
+    if rt.tail_call.is_some() {
+        return run_tail_call(rt).unwrap_or(Value::nil());
+    }
+    value
+}
+
+/// Like `evaluate`, for handlers answering the block's value as their own, e.g. `ifTrue:`. A
+/// tail call the block ends with is left for whoever sent the handler's message.
+pub fn evaluate_in_tail(rt: &mut Runtime, block: Value, args: &[Value]) -> Value {
+    let ptr = expect_block(block);
+    let count = unsafe { param_count(ptr) } as usize;
+    let args = args.get(..count).unwrap_or(args);
+    unsafe { call_block_in_tail(ptr, rt, args) }.unwrap_or(Value::nil())
+}
+
+/// The block `v` points at, for passing to `call_block`.
+pub fn expect_block(v: Value) -> *const u8 {
+    if v.type_of() != TypeId::CompiledBytecode {
+        raise(TypeId::ArgumentError, "Expected a Block argument");
+    }
+    v.as_ptr() as *const u8
+}
+
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by the compiler.
+pub unsafe fn param_count(ptr: *const u8) -> u64 {
+    unsafe { block_parts(ptr).0.param_count }
+}
+
+/// Runs a block with `args` bound to its parameters, answering the last value it left on
+/// the stack (if any) and discarding everything else it pushed.
+///
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by the compiler.
+pub unsafe fn call_block(ptr: *const u8, rt: &mut Runtime, args: &[Value]) -> Option<Value> {
+    let result = unsafe { call_block_in_tail(ptr, rt, args) };
+    if rt.tail_call.is_some() {
+        return run_tail_call(rt);
+    }
+    result
+}
+
+/// Like `call_block`, but a send the block ends with may be left in `rt.tail_call` rather
+/// than run, in which case the answer is meaningless.
+///
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by the compiler.
+pub unsafe fn call_block_in_tail(
+    ptr: *const u8,
+    rt: &mut Runtime,
+    args: &[Value],
+) -> Option<Value> {
+    let expected = unsafe { param_count(ptr) };
+    if expected != args.len() as u64 {
+        raise(
+            TypeId::ArgumentError,
+            format!(
+                "Block expects {expected} arguments, but was given {}",
+                args.len()
+            ),
+        );
+    }
+
+    let base = rt.stack.len();
+    for &arg in args {
+        rt.push_stack(arg);
+    }
+    unsafe { run_block(ptr, rt) };
+
+    let result = (rt.stack.len() > base).then(|| rt.pop_stack());
+    rt.stack.truncate(base);
+    result
 }
 
 fn define_block_loop(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 0 {
-            panic!("Bad args!")
-        }
+        expect_arg_count(arg_count, 0);
         let ptr = rt.pop_stack().as_ptr() as *const u8;
         loop {
-            unsafe { run_block(ptr, rt) };
+            unsafe { call_block(ptr, rt, &[]) };
         }
     }
     rt.register_handler("loop", handler, id);
 }
+
+/// `[ ... ] on: ZeroDivide do: [ e | ... ]` answers the receiver's value, or the handler's if
+/// the receiver signals an error of that type, see `std::error`.
+fn define_block_on_do(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [protected, error_type, handler] = rt.pop_stack_n::<3>();
+        if error_type.type_of() != TypeId::Type {
+            raise(TypeId::ArgumentError, "Expected an Error type");
+        }
+        if handler.type_of() != TypeId::CompiledBytecode {
+            raise(TypeId::ArgumentError, "Expected a Block handler");
+        }
+        let error_type = as_type_object(error_type).id;
+        on_do(rt, protected, error_type, Some(handler)).ok()
+    }
+    rt.register_handler("on:do:", handler, id);
+}
+
+/// `[ ... ] ensure: [ ... ]` runs the cleanup block however the receiver finishes, while
+/// `ifCurtailed:` only runs it when an error unwinds the receiver. Both answer the receiver's
+/// value.
+fn define_block_ensure<const ALWAYS: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const ALWAYS: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [protected, cleanup] = rt.pop_stack_n::<2>();
+        if cleanup.type_of() != TypeId::CompiledBytecode {
+            raise(TypeId::ArgumentError, "Expected a Block argument");
+        }
+        Some(ensure::<ALWAYS>(rt, protected, cleanup))
+    }
+    let selector = if ALWAYS { "ensure:" } else { "ifCurtailed:" };
+    rt.register_handler(selector, handler::<ALWAYS>, id);
+}
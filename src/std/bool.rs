@@ -1,11 +1,17 @@
 use crate::{
-    std::block::run_block,
+    std::{
+        block::evaluate_in_tail,
+        error::{expect_arg_count, raise},
+    },
     vm::{
         runtime::Runtime,
         value::{TypeId, Value},
     },
 };
 
+/// Apart from `printString`, True and False share every handler below, each reading the
+/// receiver's truth at runtime.
+/// Conditionals answer the value of the block they run, or Nil when they run none.
 pub fn define_bool(rt: &mut Runtime) {
     let true_id = TypeId::True;
     let false_id = TypeId::False;
@@ -13,33 +19,139 @@ pub fn define_bool(rt: &mut Runtime) {
     rt.define_type(true_id);
     rt.define_type(false_id);
 
-    define_if::<true>(rt, true_id);
-    define_if::<false>(rt, false_id);
+    define_print_string::<true>(rt, true_id);
+    define_print_string::<false>(rt, false_id);
+
+    for id in [true_id, false_id] {
+        define_not(rt, id);
+        define_when::<true>(rt, id);
+        define_when::<false>(rt, id);
+        define_and(rt, id);
+        define_or(rt, id);
+        define_and_then(rt, id);
+        define_or_else(rt, id);
+        define_xor(rt, id);
+        define_if_else::<true>(rt, id);
+        define_if_else::<false>(rt, id);
+    }
+}
+
+pub fn expect_bool(v: Value) -> bool {
+    if !v.is_true() && !v.is_false() {
+        raise(TypeId::ArgumentError, "Expected a True or False argument");
+    }
+    v.is_true()
 }
 
-fn define_if<const BOOLEAN: bool>(rt: &mut Runtime, id: TypeId) {
+fn define_print_string<const BOOLEAN: bool>(rt: &mut Runtime, id: TypeId) {
     fn handler<const BOOLEAN: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 1 && arg_count != 2 {
-            panic!("Expected 1 or 2 arguments, got {}", arg_count);
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        let s = if BOOLEAN { "True" } else { "False" };
+        Some(Value::from_str(s, &mut rt.heap))
+    }
+    rt.register_handler("printString", handler::<BOOLEAN>, id);
+}
+
+fn define_not(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().is_true();
+        Some(Value::from_bool(!lhs))
+    }
+    rt.register_handler("not", handler, id);
+}
+
+/// `ifTrue:` and `ifFalse:`
+fn define_when<const WHEN: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const WHEN: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        if lhs != WHEN {
+            return Some(Value::nil());
         }
+        Some(evaluate_in_tail(rt, block, &[]))
+    }
+    let selector = if WHEN { "ifTrue:" } else { "ifFalse:" };
+    rt.register_handler(selector, handler::<WHEN>, id);
+}
+
+/// Eager conjunction of two booleans.
+fn define_and(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        Some(Value::from_bool(lhs & expect_bool(arg)))
+    }
+    rt.register_handler("&", handler, id);
+}
+
+/// Eager disjunction of two booleans.
+fn define_or(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        Some(Value::from_bool(lhs | expect_bool(arg)))
+    }
+    rt.register_handler("|", handler, id);
+}
 
-        if arg_count == 1 {
-            let arg1 = rt.pop_stack().as_ptr() as *const u8;
-            if BOOLEAN {
-                unsafe { run_block(arg1, rt) };
-            }
+/// Conjunction only evaluating its block when the receiver is True.
+fn define_and_then(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        Some(if lhs {
+            evaluate_in_tail(rt, arg, &[])
         } else {
-            let arg2 = rt.pop_stack().as_ptr() as *const u8;
-            let arg1 = rt.pop_stack().as_ptr() as *const u8;
-
-            if BOOLEAN {
-                unsafe { run_block(arg1, rt) };
-            } else {
-                unsafe { run_block(arg2, rt) };
-            }
-        }
+            Value::from_bool(false)
+        })
+    }
+    rt.register_handler("and:", handler, id);
+}
+
+/// Disjunction only evaluating its block when the receiver is False.
+fn define_or_else(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        Some(if lhs {
+            Value::from_bool(true)
+        } else {
+            evaluate_in_tail(rt, arg, &[])
+        })
+    }
+    rt.register_handler("or:", handler, id);
+}
+
+fn define_xor(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        let lhs = rt.pop_stack().is_true();
+        Some(Value::from_bool(lhs ^ expect_bool(arg)))
+    }
+    rt.register_handler("xor:", handler, id);
+}
 
-        None
+/// `ifTrue:ifFalse:` and `ifFalse:ifTrue:`
+fn define_if_else<const TRUE_FIRST: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const TRUE_FIRST: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [first, second] = rt.pop_stack_n::<2>();
+        let lhs = rt.pop_stack().is_true();
+        let block = if lhs == TRUE_FIRST { first } else { second };
+        Some(evaluate_in_tail(rt, block, &[]))
     }
-    rt.register_handler("ifTrue", handler::<BOOLEAN>, id);
+    let selector = if TRUE_FIRST {
+        "ifTrue:ifFalse:"
+    } else {
+        "ifFalse:ifTrue:"
+    };
+    rt.register_handler(selector, handler::<TRUE_FIRST>, id);
 }
@@ -0,0 +1,174 @@
+use crate::{
+    std::error::{expect_arg_count, raise},
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+/// Characters are immediate Unicode scalar values, written `$a` in source.
+pub fn define_character(rt: &mut Runtime) {
+    let id = TypeId::Character;
+    rt.define_type(id);
+
+    define_character_value(rt, id);
+    define_character_as_string(rt, id, "asString");
+    define_character_as_string(rt, id, "displayString");
+    define_character_print_string(rt, id);
+
+    define_character_is_letter(rt, id);
+    define_character_is_digit(rt, id);
+    define_character_is_vowel(rt, id);
+    define_character_is_uppercase(rt, id);
+    define_character_is_lowercase(rt, id);
+    define_character_is_whitespace(rt, id);
+    define_character_case::<true>(rt, id);
+    define_character_case::<false>(rt, id);
+
+    define_character_equality::<true>(rt, id);
+    define_character_equality::<false>(rt, id);
+    define_character_lt(rt, id);
+    define_character_gt(rt, id);
+}
+
+fn define_character_value(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().as_char();
+        Some(Value::from_int(lhs as i32))
+    }
+    rt.register_handler("value", handler, id);
+}
+
+fn define_character_as_string(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().as_char();
+        Some(Value::from_str(lhs.encode_utf8(&mut [0; 4]), &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+fn define_character_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().as_char();
+        Some(Value::from_str(&format!("${lhs}"), &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
+
+fn define_character_is_letter(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(c.is_alphabetic()))
+    }
+    rt.register_handler("isLetter", handler, id);
+}
+
+fn define_character_is_digit(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(c.is_numeric()))
+    }
+    rt.register_handler("isDigit", handler, id);
+}
+
+fn define_character_is_vowel(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(matches!(
+            c.to_ascii_lowercase(),
+            'a' | 'e' | 'i' | 'o' | 'u'
+        )))
+    }
+    rt.register_handler("isVowel", handler, id);
+}
+
+fn define_character_is_uppercase(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(c.is_uppercase()))
+    }
+    rt.register_handler("isUppercase", handler, id);
+}
+
+fn define_character_is_lowercase(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(c.is_lowercase()))
+    }
+    rt.register_handler("isLowercase", handler, id);
+}
+
+fn define_character_is_whitespace(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let c = rt.pop_stack().as_char();
+        Some(Value::from_bool(c.is_whitespace()))
+    }
+    rt.register_handler("isWhitespace", handler, id);
+}
+
+/// Case conversion keeps the receiver when the mapping isn't a single character (e.g. `ß`).
+fn define_character_case<const UPPER: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const UPPER: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().as_char();
+        let mut mapped: Vec<char> = if UPPER {
+            lhs.to_uppercase().collect()
+        } else {
+            lhs.to_lowercase().collect()
+        };
+        let c = match mapped.len() {
+            1 => mapped.pop().unwrap(),
+            _ => lhs,
+        };
+        Some(Value::from_char(c))
+    }
+    let selector = if UPPER { "asUppercase" } else { "asLowercase" };
+    rt.register_handler(selector, handler::<UPPER>, id);
+}
+
+/// Unlike the orderings, equality with a non-character answers rather than raising.
+fn define_character_equality<const EQ: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EQ: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        let lhs = rt.pop_stack().as_char();
+        let equal = rhs.is_char() && lhs == rhs.as_char();
+        Some(Value::from_bool(equal == EQ))
+    }
+    rt.register_handler(if EQ { "==" } else { "!=" }, handler::<EQ>, id);
+}
+
+fn define_character_lt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        if !rhs.is_char() {
+            raise(TypeId::ArgumentError, "Expected a Character argument");
+        }
+        let lhs = rt.pop_stack().as_char();
+        Some(Value::from_bool(lhs < rhs.as_char()))
+    }
+    rt.register_handler("<", handler, id);
+}
+
+fn define_character_gt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        if !rhs.is_char() {
+            raise(TypeId::ArgumentError, "Expected a Character argument");
+        }
+        let lhs = rt.pop_stack().as_char();
+        Some(Value::from_bool(lhs > rhs.as_char()))
+    }
+    rt.register_handler(">", handler, id);
+}
@@ -0,0 +1,474 @@
+use crate::{
+    panic_message,
+    std::{
+        block::evaluate,
+        string::{as_string, expect_string},
+    },
+    vm::{
+        runtime::{ActiveSignal, ExceptionHandler, Runtime, as_type_object},
+        value::{TypeId, Value},
+    },
+};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Errors are objects whose type inherits from `Error`. Signaling one runs the innermost
+/// `on:do:` handler for its type right where it was signaled, which then decides what happens:
+///
+/// ```text
+/// n = [ ZeroDivide signal: "no"; ] on: ZeroDivide do: [ e | e resume: 0; ];
+/// ```
+///
+/// - `e return: v` unwinds to the `on:do:`, which answers `v`, as does the handler's own value
+///   if it doesn't choose
+/// - `e retry` unwinds to the `on:do:` and runs its receiver again
+/// - `e resume: v` continues after the `signal`, which answers `v`
+///
+/// Errors raised by std handlers, e.g. a `ZeroDivide` from `1 / 0`, unwind before they are
+/// handled, so there is nothing to resume and `resume:` raises an `Error` instead. Unwinding runs
+/// the `ensure:` and `ifCurtailed:` blocks it passes.
+pub fn define_error(rt: &mut Runtime) {
+    let id = TypeId::Error;
+    rt.define_type(id);
+    for subtype in [
+        TypeId::MessageNotUnderstood,
+        TypeId::ZeroDivide,
+        TypeId::IndexOutOfBounds,
+        TypeId::ArgumentError,
+        TypeId::StackOverflow,
+        TypeId::Interrupted,
+        TypeId::BudgetExceeded,
+        TypeId::OutOfMemory,
+    ] {
+        rt.define_builtin_subtype(subtype, id);
+    }
+
+    define_error_message_text(rt, id);
+    define_error_set_message_text(rt, id);
+    define_error_signal::<false>(rt, id);
+    define_error_signal::<true>(rt, id);
+    define_error_retry(rt, id);
+    define_error_return(rt, id);
+    define_error_resume(rt, id);
+    define_type_signal::<false>(rt, TypeId::Type);
+    define_type_signal::<true>(rt, TypeId::Type);
+
+    define_result_handlers::<false>(rt, TypeId::Object);
+    define_result_handlers::<true>(rt, id);
+}
+
+/// Handlers may also answer an error instead of signaling it, leaving callers to check the
+/// result, conventionally for selectors ending in `!`:
+///
+/// ```text
+/// r = jane verifyEmail!: address;
+/// r ifError: [ e | Log append: e messageText; ];
+/// ```
+///
+/// Errors run the `ifError:` block, and every other object answers itself.
+fn define_result_handlers<const IS_ERROR: bool>(rt: &mut Runtime, id: TypeId) {
+    define_is_error::<IS_ERROR>(rt, id);
+    define_if_error::<IS_ERROR>(rt, id);
+    define_if_error_if_not_error::<IS_ERROR>(rt, id);
+    define_or_else::<IS_ERROR>(rt, id);
+}
+
+/// An error raised by native code without a runtime at hand. It becomes an instance of
+/// `type_id` when it reaches an `on:do:`.
+pub struct RaisedError {
+    pub type_id: TypeId,
+    pub message: String,
+}
+
+/// An error no handler took, unwinding all the way to whoever ran the code.
+pub struct UnhandledError {
+    pub error: Value,
+    pub message: String,
+}
+
+/// Unwinds to the `on:do:` that installed the handler with id `target`.
+struct Unwind {
+    target: u64,
+    action: Action,
+}
+
+/// Unwinds to the `signal` of `error`, which then answers `value`.
+struct Resume {
+    error: Value,
+    value: Value,
+}
+
+/// What a handler decided to do about an error.
+#[derive(Clone, Copy)]
+enum Action {
+    Return(Value),
+    Retry,
+    Resume(Value),
+}
+
+/// Raises an error of `type_id` from native code. Unlike `panic!`, this doesn't report the
+/// error, since a handler may yet take it.
+pub fn raise(type_id: TypeId, message: impl Into<String>) -> ! {
+    panic::resume_unwind(Box::new(RaisedError {
+        type_id,
+        message: message.into(),
+    }))
+}
+
+/// Raises an `ArgumentError` unless a handler was sent the `expected` number of arguments.
+pub fn expect_arg_count(arg_count: u64, expected: u64) {
+    if arg_count != expected {
+        raise(
+            TypeId::ArgumentError,
+            format!("Expected {expected} arguments, got {arg_count}"),
+        );
+    }
+}
+
+pub fn is_error(rt: &Runtime, v: Value) -> bool {
+    rt.inherits_from(v.type_of(), TypeId::Error)
+}
+
+/// A new instance of the error type `type_id`.
+pub fn new_error(rt: &mut Runtime, type_id: TypeId, message: Option<&str>) -> Value {
+    let text = message.map_or(Value::nil(), |m| Value::from_str(m, &mut rt.heap));
+    let ptr = rt.heap.alloc(8, type_id);
+    unsafe { ptr.cast::<Value>().write(text) };
+    Value::from_ptr(ptr.as_ptr() as u64)
+}
+
+/// Every instance has a single slot, which errors use for their message.
+fn message_slot(error: Value) -> *mut Value {
+    error.as_ptr() as *mut Value
+}
+
+/// The error's message, or the name of its type when it was signaled without one.
+pub fn message_text(rt: &Runtime, error: Value) -> &'static str {
+    let text = unsafe { *message_slot(error) };
+    if text.is_nil() {
+        rt.type_name(error.type_of())
+    } else {
+        as_string(text)
+    }
+}
+
+/// Signals `error`, answering the value it is resumed with.
+pub fn signal(rt: &mut Runtime, error: Value) -> Value {
+    let type_id = error.type_of();
+    let found = rt
+        .exceptions
+        .handlers
+        .iter()
+        .rposition(|h| rt.inherits_from(type_id, h.error_type));
+    let Some(index) = found else {
+        panic::resume_unwind(Box::new(UnhandledError {
+            error,
+            message: message_text(rt, error).to_string(),
+        }))
+    };
+    let handler = rt.exceptions.handlers[index];
+    if handler.block.is_nil() {
+        unwind(handler.id, Action::Return(error));
+    }
+
+    // The handler and those inside it are disabled while it runs, so that errors it signals
+    // go to the handlers outside it
+    let disabled = rt.exceptions.handlers.split_off(index);
+    match run_handler(rt, handler, error, true) {
+        Action::Resume(value) => {
+            rt.exceptions.handlers.extend(disabled);
+            value
+        }
+        action => unwind(handler.id, action),
+    }
+}
+
+fn unwind(target: u64, action: Action) -> ! {
+    panic::resume_unwind(Box::new(Unwind { target, action }))
+}
+
+/// Runs `handler`'s block with `error`, answering what it decided to do.
+fn run_handler(
+    rt: &mut Runtime,
+    handler: ExceptionHandler,
+    error: Value,
+    resumable: bool,
+) -> Action {
+    rt.exceptions.signals.push(ActiveSignal {
+        error,
+        handler: handler.id,
+        resumable,
+    });
+    let checkpoint = rt.checkpoint();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| evaluate(rt, handler.block, &[error])));
+    rt.restore(checkpoint);
+    rt.exceptions.signals.pop();
+
+    let payload = match result {
+        Ok(value) => return Action::Return(value),
+        Err(payload) => payload,
+    };
+    let payload = match payload.downcast::<Unwind>() {
+        Ok(unwind) if unwind.target == handler.id => return unwind.action,
+        Ok(unwind) => panic::resume_unwind(unwind),
+        Err(payload) => payload,
+    };
+    let payload = match payload.downcast::<Resume>() {
+        Ok(resume) if resume.error == error => return Action::Resume(resume.value),
+        Ok(resume) => panic::resume_unwind(resume),
+        Err(payload) => payload,
+    };
+    // Errors raised by the handler itself go to the handlers outside it
+    match error_from_payload(rt, &*payload) {
+        Some(inner) => Action::Return(signal(rt, inner)),
+        None => panic::resume_unwind(payload),
+    }
+}
+
+/// The error object for an error that unwound with `payload`, if it can be handled. Plain
+/// panics become instances of `Error`.
+fn error_from_payload(rt: &mut Runtime, payload: &(dyn Any + Send)) -> Option<Value> {
+    if let Some(raised) = payload.downcast_ref::<RaisedError>() {
+        Some(new_error(rt, raised.type_id, Some(&raised.message)))
+    } else if payload.is::<&str>() || payload.is::<String>() {
+        let message = panic_message(payload);
+        Some(new_error(rt, TypeId::Error, Some(&message)))
+    } else {
+        None
+    }
+}
+
+/// Evaluates `protected`, handling errors of `error_type` signaled while it runs with
+/// `handler`. Without a handler, an error is caught and answered as `Err` instead.
+pub fn on_do(
+    rt: &mut Runtime,
+    protected: Value,
+    error_type: TypeId,
+    handler: Option<Value>,
+) -> Result<Value, Value> {
+    let block = handler.unwrap_or(Value::nil());
+    loop {
+        let handler = ExceptionHandler {
+            id: rt.exceptions.next_id(),
+            error_type,
+            block,
+        };
+        let checkpoint = rt.checkpoint();
+        rt.exceptions.handlers.push(handler);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| evaluate(rt, protected, &[])));
+        rt.restore(checkpoint);
+
+        let payload = match result {
+            Ok(value) => return Ok(value),
+            Err(payload) => payload,
+        };
+        let action = match payload.downcast::<Unwind>() {
+            Ok(unwind) if unwind.target == handler.id => unwind.action,
+            Ok(unwind) => panic::resume_unwind(unwind),
+            Err(payload) => match error_from_payload(rt, &*payload) {
+                Some(error) if rt.inherits_from(error.type_of(), error_type) => {
+                    if block.is_nil() {
+                        Action::Return(error)
+                    } else {
+                        run_handler(rt, handler, error, false)
+                    }
+                }
+                _ => panic::resume_unwind(payload),
+            },
+        };
+        match action {
+            Action::Return(error) if block.is_nil() => return Err(error),
+            Action::Return(value) | Action::Resume(value) => return Ok(value),
+            Action::Retry => continue,
+        }
+    }
+}
+
+/// Evaluates `protected`, then `cleanup` if it finished normally (when `ALWAYS`) or unwound
+/// because of an error (always).
+pub fn ensure<const ALWAYS: bool>(rt: &mut Runtime, protected: Value, cleanup: Value) -> Value {
+    let checkpoint = rt.checkpoint();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| evaluate(rt, protected, &[])));
+    match result {
+        Ok(value) => {
+            if ALWAYS {
+                evaluate(rt, cleanup, &[]);
+            }
+            value
+        }
+        Err(payload) => {
+            rt.restore(checkpoint);
+            evaluate(rt, cleanup, &[]);
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+fn define_is_error<const IS_ERROR: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const IS_ERROR: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        Some(Value::from_bool(IS_ERROR))
+    }
+    rt.register_handler("isError", handler::<IS_ERROR>, id);
+}
+
+/// Errors answer the value of the block, which is given the error; anything else answers
+/// itself.
+fn define_if_error<const IS_ERROR: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const IS_ERROR: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [receiver, block] = rt.pop_stack_n::<2>();
+        if IS_ERROR {
+            Some(evaluate(rt, block, &[receiver]))
+        } else {
+            Some(receiver)
+        }
+    }
+    rt.register_handler("ifError:", handler::<IS_ERROR>, id);
+}
+
+/// Runs whichever block applies, giving it the receiver.
+fn define_if_error_if_not_error<const IS_ERROR: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const IS_ERROR: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [receiver, if_error, if_not_error] = rt.pop_stack_n::<3>();
+        let block = if IS_ERROR { if_error } else { if_not_error };
+        Some(evaluate(rt, block, &[receiver]))
+    }
+    rt.register_handler("ifError:ifNotError:", handler::<IS_ERROR>, id);
+}
+
+/// `r orElse: default` answers `default` in place of an error, or its value if it is a Block
+/// (which is given the error). Anything else answers itself.
+fn define_or_else<const IS_ERROR: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const IS_ERROR: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [receiver, default] = rt.pop_stack_n::<2>();
+        if !IS_ERROR {
+            Some(receiver)
+        } else if default.type_of() == TypeId::CompiledBytecode {
+            Some(evaluate(rt, default, &[receiver]))
+        } else {
+            Some(default)
+        }
+    }
+    rt.register_handler("orElse:", handler::<IS_ERROR>, id);
+}
+
+fn expect_error(rt: &Runtime, v: Value) -> Value {
+    if !is_error(rt, v) {
+        raise(TypeId::ArgumentError, "Expected an Error");
+    }
+    v
+}
+
+/// The handler running for `error`.
+fn active_signal(rt: &Runtime, error: Value) -> ActiveSignal {
+    rt.exceptions
+        .signals
+        .iter()
+        .rev()
+        .find(|s| s.error == error)
+        .copied()
+        .unwrap_or_else(|| {
+            let name = rt.type_name(error.type_of());
+            raise(TypeId::Error, format!("This {name} is not being handled"))
+        })
+}
+
+fn define_error_message_text(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let error = rt.pop_stack();
+        let text = message_text(rt, error);
+        Some(Value::from_str(text, &mut rt.heap))
+    }
+    rt.register_handler("messageText", handler, id);
+}
+
+fn define_error_set_message_text(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [error, text] = rt.pop_stack_n::<2>();
+        expect_string(text);
+        unsafe { message_slot(error).write(text) };
+        None
+    }
+    rt.register_handler("messageText:", handler, id);
+}
+
+/// `e signal` and `e signal: "message"` answer the value the error is resumed with.
+fn define_error_signal<const MESSAGE: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const MESSAGE: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, MESSAGE as u64);
+        if MESSAGE {
+            let text = rt.pop_stack();
+            expect_string(text);
+            unsafe { message_slot(*rt.peek()).write(text) };
+        }
+        let error = rt.pop_stack();
+        Some(signal(rt, error))
+    }
+    let selector = if MESSAGE { "signal:" } else { "signal" };
+    rt.register_handler(selector, handler::<MESSAGE>, id);
+}
+
+/// `ZeroDivide signal: "message"` signals a new instance of the type.
+fn define_type_signal<const MESSAGE: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const MESSAGE: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, MESSAGE as u64);
+        let message = MESSAGE.then(|| expect_string(rt.pop_stack()));
+        let type_id = as_type_object(rt.pop_stack()).id;
+        if !rt.inherits_from(type_id, TypeId::Error) {
+            let name = rt.type_name(type_id);
+            raise(
+                TypeId::ArgumentError,
+                format!("{name} is not an Error type"),
+            );
+        }
+        let error = new_error(rt, type_id, message);
+        Some(signal(rt, error))
+    }
+    let selector = if MESSAGE { "signal:" } else { "signal" };
+    rt.register_handler(selector, handler::<MESSAGE>, id);
+}
+
+fn define_error_retry(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let error = rt.pop_stack();
+        let error = expect_error(rt, error);
+        unwind(active_signal(rt, error).handler, Action::Retry)
+    }
+    rt.register_handler("retry", handler, id);
+}
+
+fn define_error_return(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [error, value] = rt.pop_stack_n::<2>();
+        let error = expect_error(rt, error);
+        unwind(active_signal(rt, error).handler, Action::Return(value))
+    }
+    rt.register_handler("return:", handler, id);
+}
+
+fn define_error_resume(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [error, value] = rt.pop_stack_n::<2>();
+        let error = expect_error(rt, error);
+        if !active_signal(rt, error).resumable {
+            let name = rt.type_name(error.type_of());
+            raise(
+                TypeId::Error,
+                format!("This {name} was raised natively and can't be resumed"),
+            );
+        }
+        panic::resume_unwind(Box::new(Resume { error, value }))
+    }
+    rt.register_handler("resume:", handler, id);
+}
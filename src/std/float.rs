@@ -0,0 +1,163 @@
+use crate::{
+    std::{
+        error::expect_arg_count,
+        number::{self, integral},
+    },
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+pub fn define_float(rt: &mut Runtime) {
+    let id = TypeId::Float;
+    rt.define_type(id);
+
+    number::define_arithmetic(rt, id);
+
+    define_float_floor(rt, id);
+    define_float_ceiling(rt, id);
+    define_float_rounded(rt, id);
+    define_float_truncated(rt, id);
+    define_float_abs(rt, id);
+    define_float_negated(rt, id);
+    define_float_sqrt(rt, id);
+    define_float_sin(rt, id);
+    define_float_cos(rt, id);
+    define_float_tan(rt, id);
+    define_float_exp(rt, id);
+    define_float_ln(rt, id);
+    define_float_is_nan(rt, id);
+    define_float_print_string(rt, id);
+}
+
+/// Formats a float so that it always reads back as a Float (`3.0`, never `3`).
+pub fn format_float(f: f64) -> String {
+    format!("{f:?}")
+}
+
+fn define_float_floor(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(integral(f.floor(), &mut rt.heap))
+    }
+    rt.register_handler("floor", handler, id);
+}
+
+fn define_float_ceiling(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(integral(f.ceil(), &mut rt.heap))
+    }
+    rt.register_handler("ceiling", handler, id);
+}
+
+fn define_float_rounded(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(integral(f.round(), &mut rt.heap))
+    }
+    rt.register_handler("rounded", handler, id);
+}
+
+fn define_float_truncated(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(integral(f.trunc(), &mut rt.heap))
+    }
+    rt.register_handler("truncated", handler, id);
+}
+
+fn define_float_abs(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.abs()))
+    }
+    rt.register_handler("abs", handler, id);
+}
+
+fn define_float_negated(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(-f))
+    }
+    rt.register_handler("negated", handler, id);
+}
+
+fn define_float_sqrt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.sqrt()))
+    }
+    rt.register_handler("sqrt", handler, id);
+}
+
+fn define_float_sin(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.sin()))
+    }
+    rt.register_handler("sin", handler, id);
+}
+
+fn define_float_cos(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.cos()))
+    }
+    rt.register_handler("cos", handler, id);
+}
+
+fn define_float_tan(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.tan()))
+    }
+    rt.register_handler("tan", handler, id);
+}
+
+fn define_float_exp(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.exp()))
+    }
+    rt.register_handler("exp", handler, id);
+}
+
+fn define_float_ln(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_float(f.ln()))
+    }
+    rt.register_handler("ln", handler, id);
+}
+
+fn define_float_is_nan(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let f = rt.pop_stack().as_float();
+        Some(Value::from_bool(f.is_nan()))
+    }
+    rt.register_handler("isNaN", handler, id);
+}
+
+fn define_float_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack().as_float();
+        Some(Value::from_str(&format_float(lhs), &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
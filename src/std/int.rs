@@ -1,88 +1,221 @@
-use crate::vm::{
-    runtime::Runtime,
-    value::{TypeId, Value},
+use crate::{
+    std::{
+        block::{call_block, expect_block},
+        error::{expect_arg_count, raise},
+        interval,
+        number::{self, integer},
+    },
+    vm::{
+        bigint::BigInt,
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
 };
 
+/// Ints are immediate `i32`s, and results outside that range answer a LargeInt.
 pub fn define_int(rt: &mut Runtime) {
     let id = TypeId::Int;
     rt.define_type(id);
 
-    define_int_add(rt, id);
+    number::define_arithmetic(rt, id);
     define_int_sumall(rt, id);
-    define_int_display(rt, id);
-    define_int_mul(rt, id);
-    define_int_eq(rt, id);
-    define_int_neq(rt, id);
+
+    define_int_bit_and(rt, id);
+    define_int_bit_or(rt, id);
+    define_int_bit_xor(rt, id);
+    define_int_bit_shift(rt, id);
+
+    define_int_abs(rt, id);
+    define_int_negated(rt, id);
+    define_int_is_even(rt, id);
+    define_int_is_odd(rt, id);
+    define_int_bit_invert(rt, id);
+    define_int_as_float(rt, id);
+    define_int_as_character(rt, id);
+
+    define_int_print_string(rt, id, "printString");
+    define_int_print_string(rt, id, "asString");
+
+    define_int_to(rt, id);
+    define_int_to_do(rt, id);
+    define_int_times_repeat(rt, id);
+}
+
+fn expect_int(v: Value) -> i32 {
+    if !v.is_int() {
+        raise(TypeId::ArgumentError, "Expected an Int argument");
+    }
+    v.as_int()
 }
 
-fn define_int_add(rt: &mut Runtime, id: TypeId) {
+fn define_int_bit_and(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 1 {
-            panic!("Bad args!")
-        }
-        let rhs = rt.pop_stack().as_int();
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_int(rt.pop_stack());
         let lhs = rt.pop_stack().as_int();
-        Some(Value::from_int(lhs + rhs))
+        Some(Value::from_int(lhs & rhs))
     }
-    rt.register_handler("+", handler, id);
+    rt.register_handler("bitAnd:", handler, id);
 }
 
-fn define_int_eq(rt: &mut Runtime, id: TypeId) {
+fn define_int_bit_or(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 1 {
-            panic!("Bad args!")
-        }
-        let rhs = rt.pop_stack().as_int();
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_int(rt.pop_stack());
         let lhs = rt.pop_stack().as_int();
-        Some(Value::from_bool(lhs == rhs))
+        Some(Value::from_int(lhs | rhs))
     }
-    rt.register_handler("==", handler, id);
+    rt.register_handler("bitOr:", handler, id);
 }
 
-fn define_int_neq(rt: &mut Runtime, id: TypeId) {
+fn define_int_bit_xor(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 1 {
-            panic!("Bad args!")
-        }
-        let rhs = rt.pop_stack().as_int();
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_int(rt.pop_stack());
+        let lhs = rt.pop_stack().as_int();
+        Some(Value::from_int(lhs ^ rhs))
+    }
+    rt.register_handler("bitXor:", handler, id);
+}
+
+/// Positive shifts move left, negative shifts move right (arithmetically).
+fn define_int_bit_shift(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_int(rt.pop_stack());
         let lhs = rt.pop_stack().as_int();
-        Some(Value::from_bool(lhs != rhs))
+        Some(if rhs >= 0 {
+            number::normalize(BigInt::from_i64(lhs as i64).shl(rhs as u32), &mut rt.heap)
+        } else {
+            Value::from_int(lhs >> rhs.unsigned_abs().min(31))
+        })
+    }
+    rt.register_handler("bitShift:", handler, id);
+}
+
+fn define_int_abs(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(integer((i as i64).abs(), &mut rt.heap))
+    }
+    rt.register_handler("abs", handler, id);
+}
+
+fn define_int_negated(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(integer(-(i as i64), &mut rt.heap))
+    }
+    rt.register_handler("negated", handler, id);
+}
+
+fn define_int_is_even(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(Value::from_bool(i % 2 == 0))
+    }
+    rt.register_handler("isEven", handler, id);
+}
+
+fn define_int_is_odd(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(Value::from_bool(i % 2 != 0))
+    }
+    rt.register_handler("isOdd", handler, id);
+}
+
+fn define_int_bit_invert(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(Value::from_int(!i))
+    }
+    rt.register_handler("bitInvert", handler, id);
+}
+
+fn define_int_as_float(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        Some(Value::from_float(i as f64))
+    }
+    rt.register_handler("asFloat", handler, id);
+}
+
+fn define_int_as_character(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let i = rt.pop_stack().as_int();
+        match char::from_u32(i as u32) {
+            Some(c) => Some(Value::from_char(c)),
+            None => raise(
+                TypeId::ArgumentError,
+                format!("{i} is not a valid Character code point"),
+            ),
+        }
     }
-    rt.register_handler("!=", handler, id);
+    rt.register_handler("asCharacter", handler, id);
 }
 
 fn define_int_sumall(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        let mut sum = 0;
+        let mut sum = 0_i64;
         for _ in 0..arg_count {
-            sum += rt.pop_stack().as_int();
+            sum += expect_int(rt.pop_stack()) as i64;
         }
-        sum += rt.pop_stack().as_int();
-        Some(Value::from_int(sum))
+        sum += rt.pop_stack().as_int() as i64;
+        Some(integer(sum, &mut rt.heap))
     }
     rt.register_handler("+allof", handler, id);
 }
 
-fn define_int_mul(rt: &mut Runtime, id: TypeId) {
+fn define_int_print_string(rt: &mut Runtime, id: TypeId, selector: &'static str) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 1 {
-            panic!("Bad args!")
-        }
-        let rhs = rt.pop_stack().as_int();
+        expect_arg_count(arg_count, 0);
         let lhs = rt.pop_stack().as_int();
-        Some(Value::from_int(lhs * rhs))
+        Some(Value::from_str(&lhs.to_string(), &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+fn define_int_to(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let stop = expect_int(rt.pop_stack());
+        let start = rt.pop_stack().as_int();
+        Some(interval::new_interval(rt, start, stop))
     }
-    rt.register_handler("*", handler, id);
+    rt.register_handler("to:", handler, id);
 }
 
-fn define_int_display(rt: &mut Runtime, id: TypeId) {
+fn define_int_to_do(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 0 {
-            panic!("Bad args!")
+        expect_arg_count(arg_count, 2);
+        let block = expect_block(rt.pop_stack());
+        let stop = expect_int(rt.pop_stack());
+        let start = rt.pop_stack().as_int();
+        for i in start..=stop {
+            unsafe { call_block(block, rt, &[Value::from_int(i)]) };
+        }
+        None
+    }
+    rt.register_handler("to:do:", handler, id);
+}
+
+fn define_int_times_repeat(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = expect_block(rt.pop_stack());
+        let count = rt.pop_stack().as_int();
+        for _ in 0..count {
+            unsafe { call_block(block, rt, &[]) };
         }
-        let lhs = rt.pop_stack().as_int();
-        println!("(Int) {lhs}");
         None
     }
-    rt.register_handler("display", handler, id);
+    rt.register_handler("timesRepeat:", handler, id);
 }
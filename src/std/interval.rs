@@ -0,0 +1,94 @@
+use crate::{
+    std::{
+        block::{call_block, expect_block},
+        error::expect_arg_count,
+        number::integer,
+    },
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+pub fn define_interval(rt: &mut Runtime) {
+    let id = TypeId::Interval;
+    rt.define_type(id);
+
+    define_interval_do(rt, id);
+    define_interval_size(rt, id);
+    define_interval_bound::<true>(rt, id);
+    define_interval_bound::<false>(rt, id);
+    define_interval_includes(rt, id);
+    define_interval_print_string(rt, id);
+}
+
+/// Allocates an inclusive `start..=stop` range; the bounds live in the 16-byte header.
+pub fn new_interval(rt: &mut Runtime, start: i32, stop: i32) -> Value {
+    let ptr = rt.heap.alloc(16, TypeId::Interval);
+    unsafe {
+        ptr.cast::<i64>().write(start as i64);
+        ptr.cast::<i64>().add(1).write(stop as i64);
+    }
+    Value::from_ptr(ptr.as_ptr() as u64)
+}
+
+pub fn bounds(v: Value) -> (i32, i32) {
+    let ptr = v.as_ptr() as *const i64;
+    unsafe { (ptr.read() as i32, ptr.add(1).read() as i32) }
+}
+
+fn define_interval_do(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = expect_block(rt.pop_stack());
+        let (start, stop) = bounds(rt.pop_stack());
+        for i in start..=stop {
+            unsafe { call_block(block, rt, &[Value::from_int(i)]) };
+        }
+        None
+    }
+    rt.register_handler("do:", handler, id);
+}
+
+fn define_interval_size(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let (start, stop) = bounds(rt.pop_stack());
+        let size = (stop as i64 - start as i64 + 1).max(0);
+        Some(integer(size, &mut rt.heap))
+    }
+    rt.register_handler("size", handler, id);
+}
+
+fn define_interval_bound<const FIRST: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const FIRST: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let (start, stop) = bounds(rt.pop_stack());
+        Some(Value::from_int(if FIRST { start } else { stop }))
+    }
+    rt.register_handler(if FIRST { "first" } else { "last" }, handler::<FIRST>, id);
+}
+
+fn define_interval_includes(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let n = rt.pop_stack();
+        let (start, stop) = bounds(rt.pop_stack());
+        Some(Value::from_bool(
+            n.is_int() && (start..=stop).contains(&n.as_int()),
+        ))
+    }
+    rt.register_handler("includes:", handler, id);
+}
+
+fn define_interval_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let (start, stop) = bounds(rt.pop_stack());
+        Some(Value::from_str(
+            &format!("({start} to: {stop})"),
+            &mut rt.heap,
+        ))
+    }
+    rt.register_handler("printString", handler, id);
+}
@@ -0,0 +1,92 @@
+use crate::{
+    std::{
+        error::expect_arg_count,
+        number::{self, normalize},
+    },
+    vm::{
+        bigint::BigInt,
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+use std::slice;
+
+/// LargeInts hold integers outside the `i32` range, and results that fit become Ints again.
+pub fn define_large_int(rt: &mut Runtime) {
+    let id = TypeId::LargeInt;
+    rt.define_type(id);
+
+    number::define_arithmetic(rt, id);
+
+    define_large_int_abs(rt, id);
+    define_large_int_negated(rt, id);
+    define_large_int_is_even(rt, id);
+    define_large_int_is_odd(rt, id);
+    define_large_int_as_float(rt, id);
+    define_large_int_print_string(rt, id, "printString");
+    define_large_int_print_string(rt, id, "asString");
+}
+
+pub fn as_bigint(v: Value) -> BigInt {
+    let ptr = v.as_ptr() as *const u8;
+    unsafe {
+        let len = ptr.cast::<u64>().read();
+        let negative = ptr.cast::<u64>().add(1).read() != 0;
+        let limbs = slice::from_raw_parts(ptr.add(16).cast::<u32>(), len as usize);
+        BigInt::from_limbs(negative, limbs.to_vec())
+    }
+}
+
+fn define_large_int_abs(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let n = as_bigint(rt.pop_stack());
+        Some(normalize(n.abs(), &mut rt.heap))
+    }
+    rt.register_handler("abs", handler, id);
+}
+
+fn define_large_int_negated(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let n = as_bigint(rt.pop_stack());
+        Some(normalize(n.negated(), &mut rt.heap))
+    }
+    rt.register_handler("negated", handler, id);
+}
+
+fn define_large_int_is_even(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let n = as_bigint(rt.pop_stack());
+        Some(Value::from_bool(n.is_even()))
+    }
+    rt.register_handler("isEven", handler, id);
+}
+
+fn define_large_int_is_odd(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let n = as_bigint(rt.pop_stack());
+        Some(Value::from_bool(!n.is_even()))
+    }
+    rt.register_handler("isOdd", handler, id);
+}
+
+fn define_large_int_as_float(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let n = as_bigint(rt.pop_stack());
+        Some(Value::from_float(n.to_f64()))
+    }
+    rt.register_handler("asFloat", handler, id);
+}
+
+fn define_large_int_print_string(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_bigint(rt.pop_stack());
+        Some(Value::from_str(&lhs.to_string(), &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
@@ -1,13 +1,38 @@
 use crate::vm::runtime::Runtime;
 
+pub mod array;
 pub mod block;
 pub mod bool;
+pub mod character;
+pub mod error;
+pub mod float;
 pub mod int;
+pub mod interval;
+pub mod large_int;
+pub mod nil;
+pub mod number;
+pub mod object;
 pub mod string;
+pub mod symbol;
+pub mod test_case;
+pub mod transcript;
+pub mod type_object;
 
 pub fn define_std_types(rt: &mut Runtime) {
+    object::define_object(rt);
+    type_object::define_type_object(rt);
+    error::define_error(rt);
+    nil::define_nil(rt);
     int::define_int(rt);
+    large_int::define_large_int(rt);
+    float::define_float(rt);
+    interval::define_interval(rt);
     block::define_block(rt);
     bool::define_bool(rt);
+    character::define_character(rt);
     string::define_string(rt);
+    symbol::define_symbol(rt);
+    array::define_array(rt);
+    transcript::define_transcript(rt);
+    test_case::define_test_case(rt);
 }
@@ -0,0 +1,91 @@
+use crate::{
+    std::{block::evaluate_in_tail, error::expect_arg_count},
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+/// Nil is the only value of its type. The nil-testing messages are also given defaults on
+/// `Object`, so they can be sent to any value.
+pub fn define_nil(rt: &mut Runtime) {
+    let id = TypeId::Nil;
+    rt.define_type(id);
+
+    define_nil_print_string(rt, id);
+
+    define_is_nil::<true, true>(rt, TypeId::Nil);
+    define_is_nil::<false, true>(rt, TypeId::Nil);
+    define_is_nil::<true, false>(rt, TypeId::Object);
+    define_is_nil::<false, false>(rt, TypeId::Object);
+    define_if_nil::<true>(rt, TypeId::Nil);
+    define_if_nil::<false>(rt, TypeId::Object);
+    define_if_not_nil::<true>(rt, TypeId::Nil);
+    define_if_not_nil::<false>(rt, TypeId::Object);
+    define_if_nil_if_not_nil::<true>(rt, TypeId::Nil);
+    define_if_nil_if_not_nil::<false>(rt, TypeId::Object);
+}
+
+fn define_nil_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        Some(Value::from_str("Nil", &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
+
+/// `isNil` and `notNil`
+fn define_is_nil<const IS: bool, const NIL: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const IS: bool, const NIL: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        Some(Value::from_bool(IS == NIL))
+    }
+    let selector = if IS { "isNil" } else { "notNil" };
+    rt.register_handler(selector, handler::<IS, NIL>, id);
+}
+
+/// Runs the block for Nil; anything else answers itself.
+fn define_if_nil<const NIL: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const NIL: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = rt.pop_stack();
+        let lhs = rt.pop_stack();
+        Some(if NIL {
+            evaluate_in_tail(rt, block, &[])
+        } else {
+            lhs
+        })
+    }
+    rt.register_handler("ifNil:", handler::<NIL>, id);
+}
+
+/// Runs the block, optionally given the receiver, for anything but Nil; Nil answers itself.
+fn define_if_not_nil<const NIL: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const NIL: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = rt.pop_stack();
+        let lhs = rt.pop_stack();
+        Some(if NIL {
+            lhs
+        } else {
+            evaluate_in_tail(rt, block, &[lhs])
+        })
+    }
+    rt.register_handler("ifNotNil:", handler::<NIL>, id);
+}
+
+fn define_if_nil_if_not_nil<const NIL: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const NIL: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [if_nil, if_not_nil] = rt.pop_stack_n::<2>();
+        let lhs = rt.pop_stack();
+        Some(if NIL {
+            evaluate_in_tail(rt, if_nil, &[])
+        } else {
+            evaluate_in_tail(rt, if_not_nil, &[lhs])
+        })
+    }
+    rt.register_handler("ifNil:ifNotNil:", handler::<NIL>, id);
+}
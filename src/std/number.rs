@@ -0,0 +1,366 @@
+use crate::{
+    std::{
+        error::{expect_arg_count, raise},
+        large_int::as_bigint,
+    },
+    vm::{
+        bigint::BigInt,
+        runtime::{Heap, Runtime},
+        value::{TypeId, Value},
+    },
+};
+
+/// Two numeric operands converted to the more general of their representations.
+pub enum Coerced {
+    Int(i64, i64),
+    Large(BigInt, BigInt),
+    Float(f64, f64),
+}
+
+/// An exact integer result as an Int when it fits, and as a LargeInt otherwise.
+pub fn integer(n: i64, heap: &mut Heap) -> Value {
+    match i32::try_from(n) {
+        Ok(i) => Value::from_int(i),
+        Err(_) => Value::from_bigint(&BigInt::from_i64(n), heap),
+    }
+}
+
+/// Like [`integer`], demoting a LargeInt result back to an Int when it fits.
+pub fn normalize(n: BigInt, heap: &mut Heap) -> Value {
+    match n.to_i32() {
+        Some(i) => Value::from_int(i),
+        None => Value::from_bigint(&n, heap),
+    }
+}
+
+/// Converts an already-rounded float to an integer, keeping non-finite values as Floats.
+pub fn integral(f: f64, heap: &mut Heap) -> Value {
+    if f >= i32::MIN as f64 && f <= i32::MAX as f64 {
+        Value::from_int(f as i32)
+    } else {
+        BigInt::from_f64(f).map_or_else(|| Value::from_float(f), |n| normalize(n, heap))
+    }
+}
+
+/// Position of a numeric type in the tower; higher values are more general.
+pub fn generality(v: Value) -> Option<u8> {
+    match v.type_of() {
+        TypeId::Int => Some(0),
+        TypeId::LargeInt => Some(1),
+        TypeId::Float => Some(2),
+        _ => None,
+    }
+}
+
+pub fn is_number(v: Value) -> bool {
+    generality(v).is_some()
+}
+
+pub fn as_f64(v: Value) -> f64 {
+    match v.type_of() {
+        TypeId::Int => v.as_int() as f64,
+        TypeId::LargeInt => as_bigint(v).to_f64(),
+        TypeId::Float => v.as_float(),
+        _ => raise(TypeId::ArgumentError, "Expected a number"),
+    }
+}
+
+pub fn as_large(v: Value) -> BigInt {
+    match v.type_of() {
+        TypeId::Int => BigInt::from_i64(v.as_int() as i64),
+        TypeId::LargeInt => as_bigint(v),
+        _ => raise(TypeId::ArgumentError, "Expected an integer"),
+    }
+}
+
+pub fn coerce(lhs: Value, rhs: Value) -> Coerced {
+    let (Some(l), Some(r)) = (generality(lhs), generality(rhs)) else {
+        raise(TypeId::ArgumentError, "Expected numeric operands");
+    };
+    match l.max(r) {
+        0 => Coerced::Int(lhs.as_int() as i64, rhs.as_int() as i64),
+        1 => Coerced::Large(as_large(lhs), as_large(rhs)),
+        _ => Coerced::Float(as_f64(lhs), as_f64(rhs)),
+    }
+}
+
+/// Applies the closure for the representation both operands coerce to. Ints arrive widened to
+/// `i64`, so that sums and products of two Ints are exact.
+fn binary(
+    rt: &mut Runtime,
+    arg_count: u64,
+    ints: impl FnOnce(i64, i64, &mut Heap) -> Value,
+    larges: impl FnOnce(BigInt, BigInt, &mut Heap) -> Value,
+    floats: impl FnOnce(f64, f64, &mut Heap) -> Value,
+) -> Option<Value> {
+    expect_arg_count(arg_count, 1);
+    let rhs = rt.pop_stack();
+    let lhs = rt.pop_stack();
+    Some(match coerce(lhs, rhs) {
+        Coerced::Int(l, r) => ints(l, r, &mut rt.heap),
+        Coerced::Large(l, r) => larges(l, r, &mut rt.heap),
+        Coerced::Float(l, r) => floats(l, r, &mut rt.heap),
+    })
+}
+
+fn zero_check(is_zero: bool) {
+    if is_zero {
+        raise(TypeId::ZeroDivide, "Division by zero");
+    }
+}
+
+/// Registers the arithmetic and comparison protocol shared by every numeric type.
+pub fn define_arithmetic(rt: &mut Runtime, id: TypeId) {
+    define_number_add(rt, id);
+    define_number_sub(rt, id);
+    define_number_mul(rt, id);
+    define_number_div(rt, id);
+    define_number_quo(rt, id);
+    define_number_mod(rt, id, "mod:");
+    define_number_mod(rt, id, "\\\\");
+    define_number_rem(rt, id);
+    define_number_lt(rt, id);
+    define_number_gt(rt, id);
+    define_number_le(rt, id);
+    define_number_ge(rt, id);
+    define_number_max(rt, id);
+    define_number_min(rt, id);
+    define_equality::<true>(rt, id);
+    define_equality::<false>(rt, id);
+}
+
+fn define_number_add(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| integer(l + r, heap),
+            |l, r, heap| normalize(l.add(&r), heap),
+            |l, r, _| Value::from_float(l + r),
+        )
+    }
+    rt.register_handler("+", handler, id);
+}
+
+fn define_number_sub(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| integer(l - r, heap),
+            |l, r, heap| normalize(l.sub(&r), heap),
+            |l, r, _| Value::from_float(l - r),
+        )
+    }
+    rt.register_handler("-", handler, id);
+}
+
+fn define_number_mul(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| integer(l * r, heap),
+            |l, r, heap| normalize(l.mul(&r), heap),
+            |l, r, _| Value::from_float(l * r),
+        )
+    }
+    rt.register_handler("*", handler, id);
+}
+
+/// Exact quotients of integers stay integral, everything else becomes a Float.
+fn define_number_div(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| {
+                zero_check(r == 0);
+                if l % r == 0 {
+                    integer(l / r, heap)
+                } else {
+                    Value::from_float(l as f64 / r as f64)
+                }
+            },
+            |l, r, heap| {
+                zero_check(r.is_zero());
+                let (q, rem) = l.div_rem(&r);
+                if rem.is_zero() {
+                    normalize(q, heap)
+                } else {
+                    Value::from_float(l.to_f64() / r.to_f64())
+                }
+            },
+            |l, r, _| Value::from_float(l / r),
+        )
+    }
+    rt.register_handler("/", handler, id);
+}
+
+/// Integer division, rounding towards negative infinity.
+fn define_number_quo(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| {
+                zero_check(r == 0);
+                // div_euclid only rounds towards negative infinity for positive divisors
+                integer(
+                    l.div_euclid(r) - (r < 0 && l.rem_euclid(r) != 0) as i64,
+                    heap,
+                )
+            },
+            |l, r, heap| {
+                zero_check(r.is_zero());
+                normalize(l.div_mod_floor(&r).0, heap)
+            },
+            |l, r, heap| integral((l / r).floor(), heap),
+        )
+    }
+    rt.register_handler("div:", handler, id);
+}
+
+/// Modulo with the sign of the divisor, also spelt `\\`.
+fn define_number_mod(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| {
+                zero_check(r == 0);
+                let m = l % r;
+                integer(
+                    if m != 0 && (m < 0) != (r < 0) {
+                        m + r
+                    } else {
+                        m
+                    },
+                    heap,
+                )
+            },
+            |l, r, heap| {
+                zero_check(r.is_zero());
+                normalize(l.div_mod_floor(&r).1, heap)
+            },
+            |l, r, _| Value::from_float(l - r * (l / r).floor()),
+        )
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+/// Remainder with the sign of the receiver.
+fn define_number_rem(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| {
+                zero_check(r == 0);
+                integer(l % r, heap)
+            },
+            |l, r, heap| {
+                zero_check(r.is_zero());
+                normalize(l.div_rem(&r).1, heap)
+            },
+            |l, r, _| Value::from_float(l % r),
+        )
+    }
+    rt.register_handler("rem:", handler, id);
+}
+
+fn define_number_lt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, _| Value::from_bool(l < r),
+            |l, r, _| Value::from_bool(l < r),
+            |l, r, _| Value::from_bool(l < r),
+        )
+    }
+    rt.register_handler("<", handler, id);
+}
+
+fn define_number_gt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, _| Value::from_bool(l > r),
+            |l, r, _| Value::from_bool(l > r),
+            |l, r, _| Value::from_bool(l > r),
+        )
+    }
+    rt.register_handler(">", handler, id);
+}
+
+fn define_number_le(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, _| Value::from_bool(l <= r),
+            |l, r, _| Value::from_bool(l <= r),
+            |l, r, _| Value::from_bool(l <= r),
+        )
+    }
+    rt.register_handler("<=", handler, id);
+}
+
+fn define_number_ge(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, _| Value::from_bool(l >= r),
+            |l, r, _| Value::from_bool(l >= r),
+            |l, r, _| Value::from_bool(l >= r),
+        )
+    }
+    rt.register_handler(">=", handler, id);
+}
+
+fn define_number_max(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| integer(l.max(r), heap),
+            |l, r, heap| normalize(l.max(r), heap),
+            |l, r, _| Value::from_float(l.max(r)),
+        )
+    }
+    rt.register_handler("max:", handler, id);
+}
+
+fn define_number_min(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        binary(
+            rt,
+            arg_count,
+            |l, r, heap| integer(l.min(r), heap),
+            |l, r, heap| normalize(l.min(r), heap),
+            |l, r, _| Value::from_float(l.min(r)),
+        )
+    }
+    rt.register_handler("min:", handler, id);
+}
+
+/// Numeric equality, which (unlike the ordering comparisons) answers `False` rather than
+/// failing when the argument is not a number.
+pub fn define_equality<const EQ: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EQ: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        let lhs = rt.pop_stack();
+        let equal = is_number(rhs)
+            && match coerce(lhs, rhs) {
+                Coerced::Int(l, r) => l == r,
+                Coerced::Large(l, r) => l == r,
+                Coerced::Float(l, r) => l == r,
+            };
+        Some(Value::from_bool(equal == EQ))
+    }
+    rt.register_handler(if EQ { "==" } else { "!=" }, handler::<EQ>, id);
+}
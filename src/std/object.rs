@@ -0,0 +1,120 @@
+use crate::{
+    std::{
+        error::expect_arg_count,
+        string::{as_string, expect_string, is_string},
+    },
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+use std::io::Write;
+
+/// Object is the root of the type hierarchy: lookups that fall off a type's own handlers
+/// end here, so these defaults apply to every value unless a type overrides them.
+pub fn define_object(rt: &mut Runtime) {
+    let id = TypeId::Object;
+    rt.define_type(id);
+
+    define_object_print_string(rt, id);
+    define_object_display_string(rt, id);
+    define_object_display(rt, id);
+    define_object_identical::<true>(rt, id);
+    define_object_identical::<false>(rt, id);
+    define_object_responds_to(rt, id);
+    define_object_yourself(rt, id);
+}
+
+/// The developer representation of `v`, as answered by its `printString`.
+pub fn print_string(rt: &mut Runtime, v: Value) -> &'static str {
+    send_for_string(rt, v, "printString")
+}
+
+/// The user representation of `v`, as answered by its `displayString`.
+pub fn display_string(rt: &mut Runtime, v: Value) -> &'static str {
+    send_for_string(rt, v, "displayString")
+}
+
+fn send_for_string(rt: &mut Runtime, v: Value, selector: &str) -> &'static str {
+    let s = rt
+        .send(v, selector, &[])
+        .filter(|&s| is_string(s))
+        .unwrap_or_else(|| panic!("{selector} should answer a String"));
+    as_string(s)
+}
+
+/// Answers e.g. `a Block` or `an Interval`.
+fn define_object_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack();
+        let name = rt.type_name(lhs.type_of());
+        let s = format!("{} {name}", article(name));
+        Some(Value::from_str(&s, &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
+
+pub fn article(name: &str) -> &'static str {
+    if name.starts_with(['A', 'E', 'I', 'O', 'U']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// Defaults to the developer representation; types with a friendlier form override it.
+fn define_object_display_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack();
+        let s = print_string(rt, lhs);
+        Some(Value::from_str(s, &mut rt.heap))
+    }
+    rt.register_handler("displayString", handler, id);
+}
+
+fn define_object_display(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = rt.pop_stack();
+        let s = display_string(rt, lhs);
+        writeln!(rt.out, "{s}").expect("Failed to write output");
+        None
+    }
+    rt.register_handler("display", handler, id);
+}
+
+/// Identity comparison; types with a notion of equal contents override `==`.
+fn define_object_identical<const EQ: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EQ: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [lhs, rhs] = rt.pop_stack_n::<2>();
+        Some(Value::from_bool((lhs == rhs) == EQ))
+    }
+    rt.register_handler(if EQ { "==" } else { "!=" }, handler::<EQ>, id);
+}
+
+/// Whether the receiver's type, or one of its supertypes, handles the selector given as a
+/// String or Symbol.
+fn define_object_responds_to(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let selector = expect_string(rt.pop_stack());
+        let lhs = rt.pop_stack();
+        let responds = match rt.globals.idents.find(selector) {
+            Some(selector) => rt.lookup_handler(lhs.type_of(), selector).is_some(),
+            None => false,
+        };
+        Some(Value::from_bool(responds))
+    }
+    rt.register_handler("respondsTo:", handler, id);
+}
+
+fn define_object_yourself(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        Some(rt.pop_stack())
+    }
+    rt.register_handler("yourself", handler, id);
+}
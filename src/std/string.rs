@@ -1,16 +1,57 @@
-use crate::vm::{
-    runtime::Runtime,
-    value::{TypeId, Value},
+use crate::{
+    std::{
+        array::{checked_index, new_array},
+        block::{call_block, expect_block},
+        error::{expect_arg_count, raise},
+        number,
+    },
+    vm::{
+        bigint::BigInt,
+        runtime::{Heap, Runtime},
+        value::{TypeId, Value},
+    },
 };
-use std::{slice, str};
+use std::{io::Write, slice, str};
 
 pub fn define_string(rt: &mut Runtime) {
     let id = TypeId::String;
 
     rt.define_type(id);
 
-    define_string_display(rt, id);
     define_string_output(rt, id);
+
+    define_string_size(rt, id);
+    define_string_byte_size(rt, id);
+    define_string_bytes(rt, id);
+    define_string_is_empty(rt, id);
+    define_string_concat(rt, id);
+    define_string_at(rt, id);
+    define_string_copy_from_to(rt, id);
+    define_string_index_of(rt, id);
+    define_string_includes::<true>(rt, id);
+    define_string_includes::<false>(rt, id);
+    define_string_split(rt, id);
+    define_string_lines(rt, id);
+    define_string_replace_all(rt, id);
+    define_string_do(rt, id);
+
+    define_string_as_uppercase(rt, id);
+    define_string_as_lowercase(rt, id);
+    define_string_trim(rt, id);
+    define_string_reverse(rt, id);
+    define_string_as_string(rt, id, "asString");
+    define_string_as_string(rt, id, "displayString");
+    define_string_print_string(rt, id);
+    define_string_as_symbol(rt, id);
+    define_string_as_integer(rt, id);
+    define_string_as_float(rt, id);
+
+    define_string_lt(rt, id);
+    define_string_gt(rt, id);
+    define_string_le(rt, id);
+    define_string_ge(rt, id);
+    define_string_equality::<true>(rt, id);
+    define_string_equality::<false>(rt, id);
 }
 
 pub fn as_string(v: Value) -> &'static str {
@@ -21,32 +62,356 @@ pub fn as_string(v: Value) -> &'static str {
     }
 }
 
-fn define_string_display(rt: &mut Runtime, id: TypeId) {
-    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 0 {
-            panic!("Bad args!")
-        }
+/// Whether `v` uses the string layout, so that Strings and Symbols can be mixed freely.
+pub fn is_string(v: Value) -> bool {
+    matches!(v.type_of(), TypeId::String | TypeId::Symbol)
+}
 
-        let lhs = rt.pop_stack();
-        let s = as_string(lhs);
-        println!("(String) {s}");
+pub fn expect_string(v: Value) -> &'static str {
+    if !is_string(v) {
+        raise(TypeId::ArgumentError, "Expected a String argument");
+    }
+    as_string(v)
+}
 
-        None
+/// Accepts a String, Symbol or Character argument, for operations that search for text.
+fn expect_text(v: Value, buf: &mut [u8; 4]) -> &str {
+    if v.is_char() {
+        v.as_char().encode_utf8(buf)
+    } else {
+        expect_string(v)
     }
-    rt.register_handler("display", handler, id);
+}
+
+/// Strings are indexed by Unicode scalar value; this maps a character index to its byte offset.
+fn byte_offset(s: &str, index: usize) -> usize {
+    s.char_indices().nth(index).map_or(s.len(), |(i, _)| i)
+}
+
+/// Slices a string by character range.
+fn substring(s: &str, start: usize, end: usize) -> &str {
+    &s[byte_offset(s, start)..byte_offset(s, end)]
 }
 
 fn define_string_output(rt: &mut Runtime, id: TypeId) {
     fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
-        if arg_count != 0 {
-            panic!("Bad args!")
-        }
+        expect_arg_count(arg_count, 0);
 
         let lhs = rt.pop_stack();
         let s = as_string(lhs);
-        println!("{s}");
+        writeln!(rt.out, "{s}").expect("Failed to write output");
 
         None
     }
     rt.register_handler(">>", handler, id);
 }
+
+fn define_string_size(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_int(lhs.chars().count() as i32))
+    }
+    rt.register_handler("size", handler, id);
+}
+
+fn define_string_byte_size(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_int(lhs.len() as i32))
+    }
+    rt.register_handler("byteSize", handler, id);
+}
+
+/// The UTF-8 encoding of the receiver as an Array of Ints.
+fn define_string_bytes(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        let bytes: Vec<Value> = lhs.bytes().map(|b| Value::from_int(b as i32)).collect();
+        Some(new_array(&mut rt.heap, &bytes))
+    }
+    rt.register_handler("bytes", handler, id);
+}
+
+fn define_string_is_empty(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs.is_empty()))
+    }
+    rt.register_handler("isEmpty", handler, id);
+}
+
+fn define_string_concat(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_str(&[lhs, rhs].concat(), &mut rt.heap))
+    }
+    rt.register_handler(",", handler, id);
+}
+
+fn define_string_at(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let index = rt.pop_stack();
+        let lhs = as_string(rt.pop_stack());
+        let i = checked_index(index, lhs.chars().count());
+        Some(Value::from_char(lhs.chars().nth(i).unwrap()))
+    }
+    rt.register_handler("at:", handler, id);
+}
+
+fn define_string_copy_from_to(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [from, to] = rt.pop_stack_n::<2>();
+        let lhs = as_string(rt.pop_stack());
+        let size = lhs.chars().count();
+        let start = checked_index(from, size);
+        let end = checked_index(to, size) + 1;
+        let copy = if start < end {
+            substring(lhs, start, end)
+        } else {
+            ""
+        };
+        Some(Value::from_str(copy, &mut rt.heap))
+    }
+    rt.register_handler("copyFrom:to:", handler, id);
+}
+
+fn define_string_index_of(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let mut buf = [0; 4];
+        let needle = expect_text(rt.pop_stack(), &mut buf);
+        let lhs = as_string(rt.pop_stack());
+        // 1-based like every other index, with 0 meaning "not found"
+        let index = lhs.find(needle).map_or(0, |i| lhs[..i].chars().count() + 1);
+        Some(Value::from_int(index as i32))
+    }
+    rt.register_handler("indexOf:", handler, id);
+}
+
+/// `includesSubstring:` and its shorter spelling `contains:`
+fn define_string_includes<const LONG: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let mut buf = [0; 4];
+        let needle = expect_text(rt.pop_stack(), &mut buf);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs.contains(needle)))
+    }
+    let selector = if LONG {
+        "includesSubstring:"
+    } else {
+        "contains:"
+    };
+    rt.register_handler(selector, handler, id);
+}
+
+fn new_string_array(heap: &mut Heap, parts: Vec<&str>) -> Value {
+    let parts: Vec<Value> = parts
+        .into_iter()
+        .map(|part| Value::from_str(part, heap))
+        .collect();
+    new_array(heap, &parts)
+}
+
+fn define_string_split(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let separator = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        let parts = if separator.is_empty() {
+            lhs.split_whitespace().collect()
+        } else {
+            lhs.split(separator).collect()
+        };
+        Some(new_string_array(&mut rt.heap, parts))
+    }
+    rt.register_handler("split:", handler, id);
+}
+
+fn define_string_lines(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(new_string_array(&mut rt.heap, lhs.lines().collect()))
+    }
+    rt.register_handler("lines", handler, id);
+}
+
+fn define_string_replace_all(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [from, to] = rt.pop_stack_n::<2>();
+        let (from, to) = (expect_string(from), expect_string(to));
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_str(&lhs.replace(from, to), &mut rt.heap))
+    }
+    rt.register_handler("replaceAll:with:", handler, id);
+}
+
+fn define_string_do(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let block = expect_block(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        for c in lhs.chars() {
+            unsafe { call_block(block, rt, &[Value::from_char(c)]) };
+        }
+        None
+    }
+    rt.register_handler("do:", handler, id);
+}
+
+fn define_string_as_uppercase(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(&s.to_uppercase(), &mut rt.heap))
+    }
+    rt.register_handler("asUppercase", handler, id);
+}
+
+fn define_string_as_lowercase(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(&s.to_lowercase(), &mut rt.heap))
+    }
+    rt.register_handler("asLowercase", handler, id);
+}
+
+fn define_string_trim(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(s.trim(), &mut rt.heap))
+    }
+    rt.register_handler("trim", handler, id);
+}
+
+fn define_string_reverse(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(
+            &s.chars().rev().collect::<String>(),
+            &mut rt.heap,
+        ))
+    }
+    rt.register_handler("reverse", handler, id);
+}
+
+fn define_string_as_string(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(s, &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+fn define_string_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_str(&format!("{s:?}"), &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
+
+fn define_string_as_symbol(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(Value::from_symbol(s, &mut rt.heap))
+    }
+    rt.register_handler("asSymbol", handler, id);
+}
+
+/// Parses the receiver, answering `Nil` when it isn't a valid integer.
+fn define_string_as_integer(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack()).trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        Some(match BigInt::parse_radix(digits, 10) {
+            Some(n) if negative => number::normalize(n.negated(), &mut rt.heap),
+            Some(n) => number::normalize(n, &mut rt.heap),
+            None => Value::nil(),
+        })
+    }
+    rt.register_handler("asInteger", handler, id);
+}
+
+/// Parses the receiver, answering `Nil` when it isn't a valid number.
+fn define_string_as_float(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let s = as_string(rt.pop_stack());
+        Some(s.trim().parse().map_or(Value::nil(), Value::from_float))
+    }
+    rt.register_handler("asFloat", handler, id);
+}
+
+fn define_string_lt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs < rhs))
+    }
+    rt.register_handler("<", handler, id);
+}
+
+fn define_string_gt(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs > rhs))
+    }
+    rt.register_handler(">", handler, id);
+}
+
+fn define_string_le(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs <= rhs))
+    }
+    rt.register_handler("<=", handler, id);
+}
+
+fn define_string_ge(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = expect_string(rt.pop_stack());
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_bool(lhs >= rhs))
+    }
+    rt.register_handler(">=", handler, id);
+}
+
+/// Unlike the orderings, equality with a non-string answers rather than raising. A Symbol
+/// never equals a String, matching the Symbol handler in the other direction.
+fn define_string_equality<const EQ: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EQ: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        let lhs = as_string(rt.pop_stack());
+        let equal = rhs.type_of() == TypeId::String && lhs == as_string(rhs);
+        Some(Value::from_bool(equal == EQ))
+    }
+    rt.register_handler(if EQ { "==" } else { "!=" }, handler::<EQ>, id);
+}
@@ -0,0 +1,49 @@
+use crate::{
+    std::{error::expect_arg_count, string::as_string},
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+/// Symbols are immutable names sharing the String layout; two Symbols are equal when their
+/// text is.
+pub fn define_symbol(rt: &mut Runtime) {
+    let id = TypeId::Symbol;
+    rt.define_type(id);
+
+    define_symbol_as_string(rt, id, "asString");
+    define_symbol_print_string(rt, id);
+    define_symbol_as_string(rt, id, "displayString");
+    define_symbol_eq::<true>(rt, id);
+    define_symbol_eq::<false>(rt, id);
+}
+
+fn define_symbol_as_string(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_str(lhs, &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+fn define_symbol_print_string(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let lhs = as_string(rt.pop_stack());
+        Some(Value::from_str(&format!("#{lhs}"), &mut rt.heap))
+    }
+    rt.register_handler("printString", handler, id);
+}
+
+fn define_symbol_eq<const EQ: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EQ: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let rhs = rt.pop_stack();
+        let lhs = rt.pop_stack();
+        let equal = rhs.type_of() == TypeId::Symbol && as_string(lhs) == as_string(rhs);
+        Some(Value::from_bool(equal == EQ))
+    }
+    rt.register_handler(if EQ { "==" } else { "!=" }, handler::<EQ>, id);
+}
@@ -0,0 +1,280 @@
+use crate::{
+    panic_message, source_line,
+    std::{
+        block::evaluate,
+        bool::expect_bool,
+        error::{expect_arg_count, on_do},
+        object::{article, print_string},
+        string::expect_string,
+    },
+    vm::{
+        bytecode::SendSite,
+        runtime::{Runtime, as_type_object},
+        value::{TypeId, Value},
+    },
+};
+use std::{
+    any::Any,
+    fmt::Write,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+/// The payload a failed assertion unwinds with, telling failures apart from other errors.
+pub struct AssertionFailure(pub String);
+
+/// How a single test ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    /// An assertion didn't hold
+    Failed(String),
+    /// Anything else went wrong, e.g. a message wasn't understood
+    Errored(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    /// e.g. `StackTests>>testPush`
+    pub name: String,
+    pub outcome: TestOutcome,
+    /// Where the statement that failed starts, if the test didn't pass
+    pub source_offset: Option<u32>,
+}
+
+/// Tests are types inheriting from `TestCase`, and each of their unary handlers whose selector
+/// starts with `test` is a test:
+///
+/// ```text
+/// StackTests = Type named: "StackTests" superclass: TestCase;
+/// StackTests instanceMessage: "testEmpty" handler: [ self | self assert: 0 equals: 0; ];
+/// ```
+///
+/// `run_tests` runs every test on a fresh instance, sending `setUp` before it and `tearDown`
+/// after it.
+pub fn define_test_case(rt: &mut Runtime) {
+    let id = TypeId::TestCase;
+    rt.define_type(id);
+
+    define_test_case_hook(rt, id, "setUp");
+    define_test_case_hook(rt, id, "tearDown");
+    define_test_case_assert::<true>(rt, id);
+    define_test_case_assert::<false>(rt, id);
+    define_test_case_assert_equals(rt, id);
+    define_test_case_should_raise(rt, id);
+}
+
+/// Ends the running test as a failure.
+pub fn fail(message: String) -> ! {
+    panic::panic_any(AssertionFailure(message))
+}
+
+/// `setUp` and `tearDown` do nothing unless a test type overrides them.
+fn define_test_case_hook(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        None
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+/// `self assert: x isEmpty;` fails unless its argument is True, `self deny: ...` unless it
+/// is False.
+fn define_test_case_assert<const EXPECTED: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const EXPECTED: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let [_, condition] = rt.pop_stack_n::<2>();
+        if expect_bool(condition) != EXPECTED {
+            let message = if EXPECTED {
+                "Assertion failed"
+            } else {
+                "Denial failed"
+            };
+            fail(message.to_string());
+        }
+        None
+    }
+    let selector = if EXPECTED { "assert:" } else { "deny:" };
+    rt.register_handler(selector, handler::<EXPECTED>, id);
+}
+
+/// `self assert: actual equals: expected;` compares the two with `==`.
+fn define_test_case_assert_equals(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [_, actual, expected] = rt.pop_stack_n::<3>();
+        let equal = rt
+            .send(actual, "==", &[expected])
+            .is_some_and(|v| v.is_true());
+        if !equal {
+            let expected = print_string(rt, expected);
+            let actual = print_string(rt, actual);
+            fail(format!("Expected {expected} but got {actual}"));
+        }
+        None
+    }
+    rt.register_handler("assert:equals:", handler, id);
+}
+
+/// `self should: [ 1 / 0 ] raise: ZeroDivide;` fails unless the block signals an error of the
+/// given type. Given a String instead, any error whose message contains it will do.
+fn define_test_case_should_raise(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [_, block, expected] = rt.pop_stack_n::<3>();
+        if expected.type_of() == TypeId::Type {
+            let error_type = as_type_object(expected).id;
+            let name = rt.type_name(error_type);
+            if on_do(rt, block, error_type, None).is_ok() {
+                fail(format!("Expected {} {name}", article(name)));
+            }
+            return None;
+        }
+
+        let expected = expect_string(expected);
+        let site = rt.site;
+        let result = catch_error(rt, |rt| {
+            evaluate(rt, block, &[]);
+        });
+        rt.site = site;
+        match result {
+            Ok(()) => fail(format!("Expected an error mentioning {expected:?}")),
+            // A failed assertion inside the block fails the test rather than counting as raised
+            Err(payload) if payload.is::<AssertionFailure>() => panic::resume_unwind(payload),
+            Err(payload) => {
+                let message = panic_message(&*payload);
+                if !message.contains(expected) {
+                    fail(format!(
+                        "Expected an error mentioning {expected:?}, but got: {message}"
+                    ));
+                }
+            }
+        }
+        None
+    }
+    rt.register_handler("should:raise:", handler, id);
+}
+
+/// Runs `f`, answering any error it raises instead of letting it unwind further and putting
+/// the runtime's stacks back the way they were if it does. `rt.site` is left pointing at where
+/// the error was raised.
+pub fn catch_error<T>(
+    rt: &mut Runtime,
+    f: impl FnOnce(&mut Runtime) -> T,
+) -> Result<T, Box<dyn Any + Send>> {
+    let checkpoint = rt.checkpoint();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(rt)));
+    if result.is_err() {
+        rt.restore(checkpoint);
+    }
+    result
+}
+
+/// Runs the tests of every type inheriting from `TestCase`, in the order the types and then
+/// the selectors were first seen.
+pub fn run_tests(rt: &mut Runtime) -> Vec<TestResult> {
+    // Errors become test results rather than being printed where they were raised
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut results = Vec::new();
+    for type_id in rt.user_types() {
+        if !rt.inherits_from(type_id, TypeId::TestCase) {
+            continue;
+        }
+        for selector in test_selectors(rt, type_id) {
+            results.push(run_test(rt, type_id, &selector));
+        }
+    }
+    panic::set_hook(hook);
+    results
+}
+
+fn test_selectors(rt: &mut Runtime, type_id: TypeId) -> Vec<Rc<str>> {
+    (0..rt.globals.idents.len() as u64)
+        .filter_map(|id| {
+            let name = rt.globals.idents.get(id)?;
+            let is_test = name.starts_with("test") && !name.contains(':');
+            (is_test && rt.lookup_handler(type_id, id).is_some()).then_some(name)
+        })
+        .collect()
+}
+
+fn run_test(rt: &mut Runtime, type_id: TypeId, selector: &str) -> TestResult {
+    let name = format!("{}>>{selector}", rt.type_name(type_id));
+    let instance = rt
+        .send(rt.type_value(type_id), "new", &[])
+        .expect("new should answer an instance");
+
+    rt.site = SendSite::default();
+    let mut result = catch_error(rt, |rt| {
+        rt.send(instance, "setUp", &[]);
+        rt.send(instance, selector, &[]);
+    });
+    let mut site = rt.site;
+    // tearDown runs even when the test didn't pass, but its own errors only count if it did
+    let teardown = catch_error(rt, |rt| {
+        rt.send(instance, "tearDown", &[]);
+    });
+    if result.is_ok() && teardown.is_err() {
+        result = teardown;
+        site = rt.site;
+    }
+
+    let outcome = match result {
+        Ok(()) => TestOutcome::Passed,
+        Err(payload) if payload.is::<AssertionFailure>() => {
+            TestOutcome::Failed(panic_message(&*payload))
+        }
+        Err(payload) => TestOutcome::Errored(panic_message(&*payload)),
+    };
+    let source_offset = match outcome {
+        TestOutcome::Passed => None,
+        _ => unsafe { site.source_offset() },
+    };
+    TestResult {
+        name,
+        outcome,
+        source_offset,
+    }
+}
+
+/// Renders one line per test, followed by the message and location of those that didn't pass.
+/// `source` is the code the tests were defined in, read from `path`.
+pub fn format_results(results: &[TestResult], source: &str, path: &str) -> String {
+    let mut out = String::new();
+    for result in results {
+        let (status, message) = match &result.outcome {
+            TestOutcome::Passed => ("ok", None),
+            TestOutcome::Failed(message) => ("FAIL", Some(message)),
+            TestOutcome::Errored(message) => ("ERROR", Some(message)),
+        };
+        writeln!(out, "{status:<6}{}", result.name).unwrap();
+        if let Some(offset) = result.source_offset {
+            let (line, text) = source_line(source, offset as usize);
+            writeln!(out, "      {path}:{line}: {text}").unwrap();
+        }
+        if let Some(message) = message {
+            writeln!(out, "      {message}").unwrap();
+        }
+    }
+    out
+}
+
+/// e.g. `5 tests: 3 passed, 1 failed, 1 error`
+pub fn summarize(results: &[TestResult]) -> String {
+    let count = |f: fn(&TestOutcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    let passed = count(|o| matches!(o, TestOutcome::Passed));
+    let failed = count(|o| matches!(o, TestOutcome::Failed(_)));
+    let errors = count(|o| matches!(o, TestOutcome::Errored(_)));
+    format!(
+        "{} {}: {passed} passed, {failed} failed, {errors} {}",
+        results.len(),
+        plural(results.len(), "test", "tests"),
+        plural(errors, "error", "errors"),
+    )
+}
+
+fn plural<'a>(n: usize, one: &'a str, many: &'a str) -> &'a str {
+    if n == 1 { one } else { many }
+}
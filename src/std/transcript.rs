@@ -0,0 +1,65 @@
+use crate::{
+    std::{error::expect_arg_count, object::display_string},
+    vm::{
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+use std::io::Write;
+
+/// `Transcript` is a global object writing to the runtime's output, without the newline
+/// `display` adds.
+pub fn define_transcript(rt: &mut Runtime) {
+    let id = TypeId::Transcript;
+    rt.define_type(id);
+
+    define_transcript_show(rt, id);
+    define_transcript_cr(rt, id);
+    define_transcript_tab(rt, id);
+    define_transcript_space(rt, id);
+
+    let ptr = rt.heap.alloc(8, id);
+    rt.define_global("Transcript", Value::from_ptr(ptr.as_ptr() as u64));
+}
+
+fn define_transcript_show(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1);
+        let arg = rt.pop_stack();
+        rt.pop_stack();
+        let s = display_string(rt, arg);
+        write!(rt.out, "{s}").expect("Failed to write output");
+        None
+    }
+    rt.register_handler("show:", handler, id);
+}
+
+fn define_transcript_cr(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        writeln!(rt.out).expect("Failed to write output");
+        None
+    }
+    rt.register_handler("cr", handler, id);
+}
+
+fn define_transcript_tab(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        write!(rt.out, "\t").expect("Failed to write output");
+        None
+    }
+    rt.register_handler("tab", handler, id);
+}
+
+fn define_transcript_space(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        rt.pop_stack();
+        write!(rt.out, " ").expect("Failed to write output");
+        None
+    }
+    rt.register_handler("space", handler, id);
+}
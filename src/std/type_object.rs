@@ -0,0 +1,133 @@
+use crate::{
+    std::{
+        block::param_count,
+        error::{expect_arg_count, new_error, raise},
+        string::{as_string, expect_string},
+    },
+    vm::{
+        optimizer::arity,
+        runtime::{Runtime, as_type_object},
+        value::{TypeId, Value},
+    },
+};
+
+/// Types are values too: `Int`, `Object` and every type made with `Type named:` are type
+/// objects, which create instances and accept new handlers.
+pub fn define_type_object(rt: &mut Runtime) {
+    let id = TypeId::Type;
+    rt.define_type(id);
+
+    define_type_named::<false>(rt, id);
+    define_type_named::<true>(rt, id);
+    define_type_new(rt, id);
+    define_type_instance_message(rt, id);
+    define_type_name(rt, id, "name");
+    define_type_name(rt, id, "printString");
+    define_type_superclass(rt, id);
+}
+
+fn expect_type(v: Value) -> TypeId {
+    if v.type_of() != TypeId::Type {
+        raise(TypeId::ArgumentError, "Expected a Type argument");
+    }
+    as_type_object(v).id
+}
+
+/// `Type named: "Point"` defines a direct subtype of `Object`, and
+/// `Type named: "Point3" superclass: Point` one of `Point`. Either way, the new type is bound
+/// to a global of the same name. Of the std types, only `Object`, `TestCase` and `Error` can
+/// be subtyped.
+fn define_type_named<const SUPERCLASS: bool>(rt: &mut Runtime, id: TypeId) {
+    fn handler<const SUPERCLASS: bool>(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 1 + SUPERCLASS as u64);
+        let superclass = if SUPERCLASS {
+            let superclass = expect_type(rt.pop_stack());
+            let extensible = superclass == TypeId::Object
+                || superclass.0 >= TypeId::FIRST_USER.0
+                || rt.inherits_from(superclass, TypeId::TestCase)
+                || rt.inherits_from(superclass, TypeId::Error);
+            if !extensible {
+                let name = rt.type_name(superclass);
+                raise(TypeId::ArgumentError, format!("Cannot subtype {name}"));
+            }
+            superclass
+        } else {
+            TypeId::Object
+        };
+        let name = expect_string(rt.pop_stack());
+        rt.pop_stack();
+        Some(rt.define_subtype(name, superclass))
+    }
+    let selector = if SUPERCLASS {
+        "named:superclass:"
+    } else {
+        "named:"
+    };
+    rt.register_handler(selector, handler::<SUPERCLASS>, id);
+}
+
+/// Instances of user types have a single slot, which errors keep their message in.
+fn define_type_new(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let type_id = expect_type(rt.pop_stack());
+        if rt.inherits_from(type_id, TypeId::Error) {
+            return Some(new_error(rt, type_id, None));
+        }
+        if type_id.0 < TypeId::FIRST_USER.0 {
+            let name = rt.type_name(type_id);
+            raise(TypeId::ArgumentError, format!("Cannot create a new {name}"));
+        }
+        let ptr = rt.heap.alloc(8, type_id);
+        unsafe { ptr.cast::<Value>().write(Value::nil()) };
+        Some(Value::from_ptr(ptr.as_ptr() as u64))
+    }
+    rt.register_handler("new", handler, id);
+}
+
+/// `Point instanceMessage: "x" handler: [ self | ... ];` installs a Block handler, which is
+/// given the receiver followed by the message's arguments and so must take one parameter more
+/// than the selector's arity.
+fn define_type_instance_message(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 2);
+        let [selector, block] = rt.pop_stack_n::<2>();
+        let type_id = expect_type(rt.pop_stack());
+        if block.type_of() != TypeId::CompiledBytecode {
+            raise(TypeId::ArgumentError, "Expected a Block handler");
+        }
+        let selector = expect_string(selector);
+        let expected = arity(selector) as u64 + 1;
+        let given = unsafe { param_count(block.as_ptr() as *const u8) };
+        if given != expected {
+            raise(
+                TypeId::ArgumentError,
+                format!(
+                    "A handler for #{selector} expects {expected} parameters, but was given {given}"
+                ),
+            );
+        }
+        let selector = rt.globals.idents.add(selector);
+        rt.register_block_handler(selector, block, type_id);
+        None
+    }
+    rt.register_handler("instanceMessage:handler:", handler, id);
+}
+
+fn define_type_name(rt: &mut Runtime, id: TypeId, selector: &'static str) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        let name = as_type_object(rt.pop_stack()).name;
+        Some(Value::from_str(as_string(name), &mut rt.heap))
+    }
+    rt.register_handler(selector, handler, id);
+}
+
+/// Answers the supertype's type object, or Nil for `Object`.
+fn define_type_superclass(rt: &mut Runtime, id: TypeId) {
+    fn handler(rt: &mut Runtime, arg_count: u64) -> Option<Value> {
+        expect_arg_count(arg_count, 0);
+        Some(as_type_object(rt.pop_stack()).superclass)
+    }
+    rt.register_handler("superclass", handler, id);
+}
@@ -0,0 +1,296 @@
+use std::{cmp::Ordering, fmt};
+
+/// Sign-magnitude arbitrary-precision integer backing `LargeInt` values.
+///
+/// Limbs are little-endian base 2^32 digits with no trailing zeros, so zero is the empty
+/// magnitude and is never negative.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn from_limbs(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        let negative = negative && !limbs.is_empty();
+        Self { negative, limbs }
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        let mag = n.unsigned_abs();
+        Self::from_limbs(n < 0, vec![mag as u32, (mag >> 32) as u32])
+    }
+
+    /// Converts a finite float, truncating any fractional part.
+    pub fn from_f64(f: f64) -> Option<Self> {
+        if !f.is_finite() {
+            return None;
+        }
+        let f = f.trunc();
+        let bits = f.abs().to_bits();
+        let exponent = ((bits >> 52) & 0x7FF) as i32;
+        if exponent == 0 {
+            return Some(Self::default());
+        }
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let shift = exponent - 1075;
+        let mag = Self::from_i64(mantissa as i64);
+        let mag = if shift >= 0 {
+            mag.shl(shift as u32)
+        } else {
+            mag.shr(shift.unsigned_abs())
+        };
+        Some(if f < 0.0 { mag.negated() } else { mag })
+    }
+
+    /// Parses unsigned digits in the given radix; callers strip signs and separators.
+    pub fn parse_radix(digits: &str, radix: u32) -> Option<Self> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut limbs = Vec::new();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix)?;
+            let mut carry = digit as u64;
+            for limb in limbs.iter_mut() {
+                let v = *limb as u64 * radix as u64 + carry;
+                *limb = v as u32;
+                carry = v >> 32;
+            }
+            if carry != 0 {
+                limbs.push(carry as u32);
+            }
+        }
+        Some(Self::from_limbs(false, limbs))
+    }
+
+    pub fn limbs(&self) -> &[u32] {
+        &self.limbs
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|l| l % 2 == 0)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let mag = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0_u64, |acc, &l| (acc << 32) | l as u64);
+        if self.negative {
+            0_i64.checked_sub_unsigned(mag)
+        } else {
+            i64::try_from(mag).ok()
+        }
+    }
+
+    pub fn to_i32(&self) -> Option<i32> {
+        self.to_i64().and_then(|n| i32::try_from(n).ok())
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mag = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0_f64, |acc, &l| acc * 4_294_967_296.0 + l as f64);
+        if self.negative { -mag } else { mag }
+    }
+
+    pub fn negated(mut self) -> Self {
+        self.negative = !self.negative && !self.limbs.is_empty();
+        self
+    }
+
+    pub fn abs(mut self) -> Self {
+        self.negative = false;
+        self
+    }
+
+    pub fn add(&self, rhs: &Self) -> Self {
+        if self.negative == rhs.negative {
+            return Self::from_limbs(self.negative, add_mag(&self.limbs, &rhs.limbs));
+        }
+        match cmp_mag(&self.limbs, &rhs.limbs) {
+            Ordering::Less => Self::from_limbs(rhs.negative, sub_mag(&rhs.limbs, &self.limbs)),
+            _ => Self::from_limbs(self.negative, sub_mag(&self.limbs, &rhs.limbs)),
+        }
+    }
+
+    pub fn sub(&self, rhs: &Self) -> Self {
+        self.add(&rhs.clone().negated())
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let mut out = vec![0_u32; self.limbs.len() + rhs.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, &b) in rhs.limbs.iter().enumerate() {
+                let v = out[i + j] as u64 + a as u64 * b as u64 + carry;
+                out[i + j] = v as u32;
+                carry = v >> 32;
+            }
+            out[i + rhs.limbs.len()] = carry as u32;
+        }
+        Self::from_limbs(self.negative != rhs.negative, out)
+    }
+
+    /// Truncating division: the quotient rounds towards zero and the remainder takes the
+    /// sign of the receiver. Panics on a zero divisor.
+    pub fn div_rem(&self, rhs: &Self) -> (Self, Self) {
+        assert!(!rhs.is_zero(), "Division by zero");
+        let (q, r) = div_rem_mag(&self.limbs, &rhs.limbs);
+        (
+            Self::from_limbs(self.negative != rhs.negative, q),
+            Self::from_limbs(self.negative, r),
+        )
+    }
+
+    /// Flooring division: the quotient rounds towards negative infinity and the remainder
+    /// takes the sign of the divisor.
+    pub fn div_mod_floor(&self, rhs: &Self) -> (Self, Self) {
+        let (q, r) = self.div_rem(rhs);
+        if !r.is_zero() && r.negative != rhs.negative {
+            (q.sub(&Self::from_i64(1)), r.add(rhs))
+        } else {
+            (q, r)
+        }
+    }
+
+    pub fn shl(&self, bits: u32) -> Self {
+        let (words, bits) = ((bits / 32) as usize, bits % 32);
+        let mut out = vec![0_u32; words];
+        let mut carry = 0_u32;
+        for &l in &self.limbs {
+            out.push((l << bits) | carry);
+            carry = if bits == 0 { 0 } else { l >> (32 - bits) };
+        }
+        out.push(carry);
+        Self::from_limbs(self.negative, out)
+    }
+
+    /// Arithmetic shift right, rounding towards negative infinity.
+    pub fn shr(&self, bits: u32) -> Self {
+        self.div_mod_floor(&Self::from_i64(1).shl(bits)).0
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.limbs, &other.limbs),
+            (true, true) => cmp_mag(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Peel off nine decimal digits at a time
+        const CHUNK: u32 = 1_000_000_000;
+        let mut mag = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !mag.is_empty() {
+            let mut rem = 0_u64;
+            for limb in mag.iter_mut().rev() {
+                let v = (rem << 32) | *limb as u64;
+                *limb = (v / CHUNK as u64) as u32;
+                rem = v % CHUNK as u64;
+            }
+            while mag.last() == Some(&0) {
+                mag.pop();
+            }
+            chunks.push(rem as u32);
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{chunk:09}")?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0_u64;
+    for (i, &l) in long.iter().enumerate() {
+        let v = l as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(v as u32);
+        carry = v >> 32;
+    }
+    out.push(carry as u32);
+    out
+}
+
+/// Requires `a >= b` in magnitude.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0_i64;
+    for (i, &l) in a.iter().enumerate() {
+        let mut v = l as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (v < 0) as i64;
+        if v < 0 {
+            v += 1 << 32;
+        }
+        out.push(v as u32);
+    }
+    out
+}
+
+/// Schoolbook binary long division; plenty fast for the sizes Howl programs produce.
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    let mut quotient = vec![0_u32; a.len()];
+    let mut rem = BigInt::default();
+    let divisor = BigInt::from_limbs(false, b.to_vec());
+    for bit in (0..a.len() * 32).rev() {
+        rem = rem.shl(1);
+        if (a[bit / 32] >> (bit % 32)) & 1 == 1 {
+            rem = rem.add(&BigInt::from_i64(1));
+        }
+        if rem >= divisor {
+            rem = rem.sub(&divisor);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, rem.limbs)
+}
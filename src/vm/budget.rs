@@ -0,0 +1,95 @@
+use crate::{std::error::raise, vm::value::TypeId};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+/// How many instructions run between checks of the deadline and the interrupt flag.
+const CHECK_INTERVAL: u64 = 1_024;
+
+/// Limits on how long code may run, for hosts running code they don't trust. Once a limit is
+/// hit every instruction raises again, so Howl code can't carry on by catching the error; the
+/// runtime is usable again once the host lifts the limit.
+///
+/// ```text
+/// rt.budget.set_fuel(Some(1_000_000));
+/// let interrupt = rt.budget.interrupt_flag();
+/// thread::spawn(move || interrupt.store(true, Ordering::Relaxed));
+/// ```
+#[derive(Debug, Default)]
+pub struct Budget {
+    /// Instructions left beyond `ticks`, or None for no limit
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    interrupt: Arc<AtomicBool>,
+    /// Instructions that may run before the next check
+    ticks: u64,
+}
+
+impl Budget {
+    /// Counts one instruction, raising `BudgetExceeded` or `Interrupted` if it may not run.
+    #[inline(always)]
+    pub fn tick(&mut self) {
+        if self.ticks == 0 {
+            self.check();
+        }
+        self.ticks -= 1;
+    }
+
+    #[cold]
+    fn check(&mut self) {
+        if self.interrupt.load(Ordering::Relaxed) {
+            raise(TypeId::Interrupted, "Interrupted");
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            raise(TypeId::BudgetExceeded, "Ran past its deadline");
+        }
+        self.ticks = match &mut self.fuel {
+            None => CHECK_INTERVAL,
+            Some(0) => raise(TypeId::BudgetExceeded, "Ran out of fuel"),
+            Some(fuel) => {
+                let ticks = (*fuel).min(CHECK_INTERVAL);
+                *fuel -= ticks;
+                ticks
+            }
+        };
+    }
+
+    /// Lets only `fuel` more instructions run, or any number with None.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+        self.ticks = 0;
+    }
+
+    /// How many more instructions may run, if that is limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel.map(|fuel| fuel + self.ticks)
+    }
+
+    /// Stops code from running once `deadline` has passed, checked every `CHECK_INTERVAL`
+    /// instructions.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.reclaim_ticks();
+        self.deadline = deadline;
+    }
+
+    /// A flag another thread can set to stop running code, checked every `CHECK_INTERVAL`
+    /// instructions. It stays set until cleared.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// Makes the next instruction check the limits, without losing any fuel.
+    fn reclaim_ticks(&mut self) {
+        if let Some(fuel) = &mut self.fuel {
+            *fuel += self.ticks;
+        }
+        self.ticks = 0;
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    IdentArena,
+    vm::{
+        runtime::{Config, Heap, Runtime},
+        value::TypeId,
+    },
+};
+use hashbrown::HashMap;
+
+/// Which std handlers of a type a sandboxed runtime keeps.
+#[derive(Clone, Debug)]
+enum Allowed {
+    All,
+    Only(Vec<&'static str>),
+}
+
+/// Builds a `Runtime`, optionally keeping only some of the std types and handlers, e.g. to run
+/// code submitted by users without letting it write output:
+///
+/// ```text
+/// let rt = RuntimeBuilder::sandboxed()
+///     .allow_type(TypeId::Int)
+///     .allow_handlers(TypeId::Object, &["==", "printString"])
+///     .heap_capacity(1 << 20)
+///     .fuel(1_000_000)
+///     .build();
+/// ```
+///
+/// Sending a message whose handler was left out raises `MessageNotUnderstood`, as for any other
+/// missing handler. Every std type still exists, so that values of it can be told apart, and
+/// handlers defined from Howl or registered after building are unaffected.
+#[derive(Clone, Debug)]
+pub struct RuntimeBuilder {
+    config: Config,
+    heap_capacity: Option<u64>,
+    fuel: Option<u64>,
+    /// None keeps every std handler
+    allowed: Option<HashMap<TypeId, Allowed>>,
+}
+
+impl RuntimeBuilder {
+    /// A builder for a runtime with every std type and handler.
+    pub fn new() -> Self {
+        Self {
+            config: Config::default(),
+            heap_capacity: None,
+            fuel: None,
+            allowed: None,
+        }
+    }
+
+    /// A builder for a runtime keeping only the std handlers allowed with `allow_type` and
+    /// `allow_handlers`.
+    pub fn sandboxed() -> Self {
+        Self {
+            allowed: Some(HashMap::new()),
+            ..Self::new()
+        }
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Limits the heap to `bytes`, after which allocations raise `OutOfMemory`. Nothing is freed,
+    /// so a runtime that hit the limit stays near it. The tables behind globals and handlers live
+    /// outside the heap and are not bounded by this.
+    pub fn heap_capacity(mut self, bytes: u64) -> Self {
+        self.heap_capacity = Some(bytes);
+        self
+    }
+
+    /// Limits how many instructions may run, see `Budget::set_fuel`.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Keeps every std handler of `type_id`. Handlers inherited from its supertypes have to be
+    /// allowed on those.
+    pub fn allow_type(mut self, type_id: TypeId) -> Self {
+        if let Some(allowed) = &mut self.allowed {
+            allowed.insert(type_id, Allowed::All);
+        }
+        self
+    }
+
+    /// Keeps the std handlers of `type_id` for `selectors`, on top of any allowed before.
+    pub fn allow_handlers(mut self, type_id: TypeId, selectors: &[&'static str]) -> Self {
+        if let Some(allowed) = &mut self.allowed {
+            match allowed.entry(type_id).or_insert(Allowed::Only(Vec::new())) {
+                Allowed::All => {}
+                Allowed::Only(only) => only.extend_from_slice(selectors),
+            }
+        }
+        self
+    }
+
+    pub fn build(self) -> Runtime {
+        let heap = match self.heap_capacity {
+            Some(bytes) => Heap::new_with_capacity(bytes),
+            None => Heap::default(),
+        };
+        let mut rt = Runtime::empty(self.config, heap);
+        crate::std::define_std_types(&mut rt);
+        if let Some(allowed) = &self.allowed {
+            for id in (0..TypeId::FIRST_USER.0).map(TypeId) {
+                if !rt.has_type(id) {
+                    continue;
+                }
+                match allowed.get(&id) {
+                    Some(Allowed::All) => {}
+                    Some(Allowed::Only(only)) => rt.retain_handlers(id, |s| only.contains(&s)),
+                    None => rt.retain_handlers(id, |_| false),
+                }
+            }
+        }
+        // The fast path adds and compares Ints without looking `+` and `<` up
+        let int_handlers = [IdentArena::ADD, IdentArena::LESS];
+        rt.globals.int_fast_path = int_handlers
+            .iter()
+            .all(|&selector| rt.lookup_handler(TypeId::Int, selector).is_some());
+        rt.budget.set_fuel(self.fuel);
+        rt
+    }
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
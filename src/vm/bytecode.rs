@@ -1,33 +1,453 @@
-use crate::vm::{
-    runtime::{HeapMap, Runtime},
-    value::Value,
+use crate::{
+    IdentArena,
+    std::{block::call_block_in_tail, error::raise, object::display_string, string::as_string},
+    vm::{
+        cache::InlineCache,
+        disassembler::disassemble,
+        optimizer::optimize,
+        runtime::{Frame, Heap, Runtime},
+        value::{TypeId, Value},
+    },
 };
-use std::{mem, ptr::NonNull};
+use std::{io::Write, mem, ptr::NonNull, slice};
 
 type ExternHandler = fn(rt: &mut Runtime, arg_count: u64) -> Option<Value>;
 pub enum Handler {
     Extern(ExternHandler),
 }
 
-#[repr(u128)]
-#[derive(Debug, Copy, Clone)]
+/// A decoded instruction. In memory, instructions are a one-byte opcode followed by LEB128
+/// operands; `PushConst` and the inline cache of every send index the chunk's constants.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum OpCode {
-    PushLit(Value),
+    PushConst(u64),
+    /// Pushes an Int literal, zigzag encoded so that small negative numbers stay short
+    PushSmallInt(i32),
     PushGlobal(u64),
     SetGlobal(u64),
-    SendMessage { id: u64, arg_count: u64 },
+    /// Like `SetGlobal`, but leaves the value on the stack
+    StoreGlobal(u64),
+    SendMessage {
+        id: u64,
+        arg_count: u64,
+        cache: u64,
+    },
+    /// Pops `n` values and concatenates their `displayString`s into a single String.
+    BuildString(u64),
+    /// Pushes the receiver of the running handler
+    PushSelf,
+    /// Sends to the running handler's receiver, starting the lookup at its holder's superclass
+    SuperSend {
+        id: u64,
+        arg_count: u64,
+    },
+    /// `+` with a single argument, added directly when both operands are Ints
+    AddInt {
+        cache: u64,
+    },
+    /// `<` with a single argument, compared directly when both operands are Ints
+    LessInt {
+        cache: u64,
+    },
+    /// A `SendMessage` without arguments
+    SendUnary0 {
+        id: u64,
+        cache: u64,
+    },
+    /// A `SendMessage` with a single argument
+    SendBinary {
+        id: u64,
+        cache: u64,
+    },
+    /// A `SendMessage` ending a block, whose value is the block's. A handler written in Howl
+    /// runs in place of the block's own handler instead of on top of it, unless
+    /// `Config::no_tail_calls` is set.
+    TailSend {
+        id: u64,
+        arg_count: u64,
+        cache: u64,
+    },
+}
+
+const PUSH_CONST: u8 = 0;
+const PUSH_GLOBAL: u8 = 1;
+const SET_GLOBAL: u8 = 2;
+const SEND_MESSAGE: u8 = 3;
+const BUILD_STRING: u8 = 4;
+const PUSH_SELF: u8 = 5;
+const SUPER_SEND: u8 = 6;
+const STORE_GLOBAL: u8 = 7;
+const PUSH_SMALL_INT: u8 = 8;
+const ADD_INT: u8 = 9;
+const LESS_INT: u8 = 10;
+const SEND_UNARY_0: u8 = 11;
+const SEND_BINARY: u8 = 12;
+const TAIL_SEND: u8 = 13;
+
+impl OpCode {
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            OpCode::PushConst(i) => encode_op(buf, PUSH_CONST, &[i]),
+            OpCode::PushGlobal(g) => encode_op(buf, PUSH_GLOBAL, &[g]),
+            OpCode::SetGlobal(g) => encode_op(buf, SET_GLOBAL, &[g]),
+            OpCode::StoreGlobal(g) => encode_op(buf, STORE_GLOBAL, &[g]),
+            OpCode::SendMessage {
+                id,
+                arg_count,
+                cache,
+            } => encode_op(buf, SEND_MESSAGE, &[id, arg_count, cache]),
+            OpCode::BuildString(n) => encode_op(buf, BUILD_STRING, &[n]),
+            OpCode::PushSelf => encode_op(buf, PUSH_SELF, &[]),
+            OpCode::SuperSend { id, arg_count } => encode_op(buf, SUPER_SEND, &[id, arg_count]),
+            OpCode::PushSmallInt(i) => encode_op(buf, PUSH_SMALL_INT, &[zigzag(i)]),
+            OpCode::AddInt { cache } => encode_op(buf, ADD_INT, &[cache]),
+            OpCode::LessInt { cache } => encode_op(buf, LESS_INT, &[cache]),
+            OpCode::SendUnary0 { id, cache } => encode_op(buf, SEND_UNARY_0, &[id, cache]),
+            OpCode::SendBinary { id, cache } => encode_op(buf, SEND_BINARY, &[id, cache]),
+            OpCode::TailSend {
+                id,
+                arg_count,
+                cache,
+            } => encode_op(buf, TAIL_SEND, &[id, arg_count, cache]),
+        }
+    }
+
+    /// The selector, argument count and inline cache of any instruction that sends through a
+    /// cache.
+    pub fn as_send(&self) -> Option<(u64, u64, u64)> {
+        match *self {
+            OpCode::SendMessage {
+                id,
+                arg_count,
+                cache,
+            } => Some((id, arg_count, cache)),
+            OpCode::AddInt { cache } => Some((IdentArena::ADD, 1, cache)),
+            OpCode::LessInt { cache } => Some((IdentArena::LESS, 1, cache)),
+            OpCode::SendUnary0 { id, cache } => Some((id, 0, cache)),
+            OpCode::SendBinary { id, cache } => Some((id, 1, cache)),
+            OpCode::TailSend {
+                id,
+                arg_count,
+                cache,
+            } => Some((id, arg_count, cache)),
+            _ => None,
+        }
+    }
+
+    /// Decodes the instruction at `pc`, advancing it past the instruction.
+    #[inline(always)]
+    pub fn decode(code: &[u8], pc: &mut usize) -> OpCode {
+        let op = code[*pc];
+        *pc += 1;
+        match op {
+            PUSH_CONST => OpCode::PushConst(read_leb128(code, pc)),
+            PUSH_GLOBAL => OpCode::PushGlobal(read_leb128(code, pc)),
+            SET_GLOBAL => OpCode::SetGlobal(read_leb128(code, pc)),
+            STORE_GLOBAL => OpCode::StoreGlobal(read_leb128(code, pc)),
+            SEND_MESSAGE => OpCode::SendMessage {
+                id: read_leb128(code, pc),
+                arg_count: read_leb128(code, pc),
+                cache: read_leb128(code, pc),
+            },
+            BUILD_STRING => OpCode::BuildString(read_leb128(code, pc)),
+            PUSH_SELF => OpCode::PushSelf,
+            SUPER_SEND => OpCode::SuperSend {
+                id: read_leb128(code, pc),
+                arg_count: read_leb128(code, pc),
+            },
+            PUSH_SMALL_INT => OpCode::PushSmallInt(unzigzag(read_leb128(code, pc))),
+            ADD_INT => OpCode::AddInt {
+                cache: read_leb128(code, pc),
+            },
+            LESS_INT => OpCode::LessInt {
+                cache: read_leb128(code, pc),
+            },
+            SEND_UNARY_0 => OpCode::SendUnary0 {
+                id: read_leb128(code, pc),
+                cache: read_leb128(code, pc),
+            },
+            SEND_BINARY => OpCode::SendBinary {
+                id: read_leb128(code, pc),
+                cache: read_leb128(code, pc),
+            },
+            TAIL_SEND => OpCode::TailSend {
+                id: read_leb128(code, pc),
+                arg_count: read_leb128(code, pc),
+                cache: read_leb128(code, pc),
+            },
+            _ => invalid_opcode(op, *pc - 1),
+        }
+    }
+}
+
+#[cold]
+fn invalid_opcode(op: u8, offset: usize) -> ! {
+    panic!("Invalid opcode {op} at {offset}")
+}
+
+fn encode_op(buf: &mut Vec<u8>, op: u8, operands: &[u64]) {
+    buf.push(op);
+    for &operand in operands {
+        write_leb128(buf, operand);
+    }
+}
+
+fn write_leb128(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn zigzag(i: i32) -> u64 {
+    ((i << 1) ^ (i >> 31)) as u32 as u64
+}
+
+#[inline(always)]
+fn unzigzag(n: u64) -> i32 {
+    let n = n as u32;
+    ((n >> 1) as i32) ^ -((n & 1) as i32)
+}
+
+#[inline(always)]
+fn read_leb128(code: &[u8], pc: &mut usize) -> u64 {
+    // Almost every operand fits in a single byte
+    let first = code[*pc];
+    *pc += 1;
+    if first & 0x80 == 0 {
+        return first as u64;
+    }
+    let mut n = (first & 0x7F) as u64;
+    let mut shift = 7;
+    loop {
+        let byte = code[*pc];
+        *pc += 1;
+        n |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return n;
+        }
+        shift += 7;
+    }
+}
+
+/// Compiled code: encoded instructions, the constant pool they index into and a table mapping
+/// code offsets back to the source.
+#[derive(Clone, Debug, Default)]
+pub struct Chunk {
+    pub consts: Vec<Value>,
+    pub code: Vec<u8>,
+    pub lines: Vec<LineEntry>,
+}
+
+/// Marks that the code from `code_offset` up to the next entry was compiled from the statement
+/// starting at byte `source_offset` of the source.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineEntry {
+    pub code_offset: u32,
+    pub source_offset: u32,
+}
+
+/// The most recent send made from bytecode, kept so that native code can tell which statement
+/// a failing message was sent from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SendSite {
+    lines: *const LineEntry,
+    line_count: usize,
+    /// Just past the send instruction
+    code_offset: usize,
+}
+
+impl SendSite {
+    /// The source offset of the statement the send belongs to, if its code has line entries.
+    ///
+    /// # Safety
+    /// The code that made the send must still be alive. Blocks always are, since the heap is
+    /// never freed, but a top-level chunk is dropped once it has run.
+    pub unsafe fn source_offset(&self) -> Option<u32> {
+        let lines = if self.lines.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.lines, self.line_count) }
+        };
+        lines
+            .iter()
+            .take_while(|entry| (entry.code_offset as usize) < self.code_offset)
+            .last()
+            .map(|entry| entry.source_offset)
+    }
+}
+
+/// A `CompiledBytecode` heap object starts with this header, followed by `const_count`
+/// constants, `line_count` line entries and then `code_len` bytes of code.
+#[repr(C)]
+pub struct BlockHeader {
+    pub code_len: u64,
+    pub param_count: u64,
+    pub const_count: u64,
+    pub line_count: u64,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, op: OpCode) {
+        op.encode(&mut self.code);
+    }
+
+    /// Adds `v` to the constant pool, reusing an identical entry when there is one.
+    pub fn add_const(&mut self, v: Value) -> u64 {
+        let index = self.consts.iter().position(|&c| c == v).unwrap_or_else(|| {
+            self.consts.push(v);
+            self.consts.len() - 1
+        });
+        index as u64
+    }
+
+    pub fn push_const(&mut self, v: Value) {
+        let index = self.add_const(v);
+        self.emit(OpCode::PushConst(index));
+    }
+
+    /// Records that the code emitted from here on belongs to the statement at `source_offset`.
+    pub fn mark_source(&mut self, source_offset: usize) {
+        let entry = LineEntry {
+            code_offset: self.code.len() as u32,
+            source_offset: source_offset as u32,
+        };
+        match self.lines.last_mut() {
+            Some(last) if last.code_offset == entry.code_offset => *last = entry,
+            _ => self.lines.push(entry),
+        }
+    }
+
+    /// Decodes every instruction along with its offset.
+    pub fn ops(&self) -> Vec<(usize, OpCode)> {
+        decode_all(&self.code)
+    }
+
+    /// Copies the chunk into a new `CompiledBytecode` object.
+    pub fn alloc_block(&self, param_count: u64, heap: &mut Heap) -> Value {
+        let header = BlockHeader {
+            code_len: self.code.len() as u64,
+            param_count,
+            const_count: self.consts.len() as u64,
+            line_count: self.lines.len() as u64,
+        };
+        let size = size_of::<BlockHeader>()
+            + size_of_val(self.consts.as_slice())
+            + size_of_val(self.lines.as_slice())
+            + self.code.len();
+        let ptr = heap.alloc(size as u64, TypeId::CompiledBytecode);
+        unsafe {
+            ptr.cast::<BlockHeader>().write(header);
+            let consts = ptr.add(size_of::<BlockHeader>()).cast::<Value>();
+            consts.copy_from_nonoverlapping(
+                NonNull::from(self.consts.as_slice()).cast(),
+                self.consts.len(),
+            );
+            let lines = consts.add(self.consts.len()).cast::<LineEntry>();
+            lines.copy_from_nonoverlapping(
+                NonNull::from(self.lines.as_slice()).cast(),
+                self.lines.len(),
+            );
+            let code = lines.add(self.lines.len()).cast::<u8>();
+            code.copy_from_nonoverlapping(
+                NonNull::from(self.code.as_slice()).cast(),
+                self.code.len(),
+            );
+        }
+        Value::from_ptr(ptr.as_ptr() as u64)
+    }
+}
+
+pub fn decode_all(code: &[u8]) -> Vec<(usize, OpCode)> {
+    let mut ops = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let offset = pc;
+        ops.push((offset, OpCode::decode(code, &mut pc)));
+    }
+    ops
+}
+
+/// Splits a `CompiledBytecode` object into its header, constants and code.
+///
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by `Chunk::alloc_block`.
+pub unsafe fn block_parts(
+    ptr: *const u8,
+) -> (&'static BlockHeader, &'static [Value], &'static [u8]) {
+    unsafe {
+        let header = &*ptr.cast::<BlockHeader>();
+        let consts = ptr.add(size_of::<BlockHeader>()).cast::<Value>();
+        let lines = consts.add(header.const_count as usize).cast::<LineEntry>();
+        let code = lines.add(header.line_count as usize).cast::<u8>();
+        (
+            header,
+            slice::from_raw_parts(consts, header.const_count as usize),
+            slice::from_raw_parts(code, header.code_len as usize),
+        )
+    }
+}
+
+/// The line table of a `CompiledBytecode` object.
+///
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by `Chunk::alloc_block`.
+pub unsafe fn block_lines(ptr: *const u8) -> &'static [LineEntry] {
+    unsafe {
+        let header = &*ptr.cast::<BlockHeader>();
+        let consts = ptr.add(size_of::<BlockHeader>()).cast::<Value>();
+        let lines = consts.add(header.const_count as usize).cast::<LineEntry>();
+        slice::from_raw_parts(lines, header.line_count as usize)
+    }
+}
+
+/// Copies a `CompiledBytecode` object back into a chunk, answering it with the block's
+/// parameter count.
+///
+/// # Safety
+/// `ptr` must point at a `CompiledBytecode` allocation produced by `Chunk::alloc_block`.
+pub unsafe fn block_chunk(ptr: *const u8) -> (Chunk, u64) {
+    let (header, consts, code) = unsafe { block_parts(ptr) };
+    let chunk = Chunk {
+        consts: consts.to_vec(),
+        code: code.to_vec(),
+        lines: unsafe { block_lines(ptr) }.to_vec(),
+    };
+    (chunk, header.param_count)
 }
 
 pub fn flush_runtime(rt: &mut Runtime) {
-    let code = mem::take(&mut rt.code);
-    println!("Compiled bytecode: {code:?}");
-    exe(code, rt);
+    let mut chunk = mem::take(&mut rt.code);
+    optimize(&mut chunk, rt);
+    if rt.config.trace {
+        let listing = disassemble(rt, &chunk, None);
+        write!(rt.err, "Compiled bytecode:\n{listing}").expect("Failed to write trace");
+    }
+    exe(&chunk.code, &chunk.consts, &chunk.lines, rt);
+    rt.site = SendSite::default();
+    rt.out.flush().expect("Failed to flush output");
 }
 
-pub fn exe<I: IntoIterator<Item = OpCode>>(code: I, rt: &mut Runtime) {
-    for op in code {
+/// Runs `code`, whose `lines` are recorded in `rt.site` by each send it makes.
+pub fn exe(code: &[u8], consts: &[Value], lines: &[LineEntry], rt: &mut Runtime) {
+    let mut pc = 0;
+    while pc < code.len() {
+        rt.budget.tick();
+        let op = OpCode::decode(code, &mut pc);
+        let site = SendSite {
+            lines: lines.as_ptr(),
+            line_count: lines.len(),
+            code_offset: pc,
+        };
         match op {
-            OpCode::PushLit(v) => rt.push_stack(v),
+            OpCode::PushConst(i) => rt.push_stack(consts[i as usize]),
+            OpCode::PushSmallInt(i) => rt.push_stack(Value::from_int(i)),
             OpCode::PushGlobal(g) => {
                 let global_val = rt.globals.vars.get(&Value::from_uint(g));
                 if let Some(v) = global_val {
@@ -43,32 +463,239 @@ pub fn exe<I: IntoIterator<Item = OpCode>>(code: I, rt: &mut Runtime) {
                 let stack_value = rt.pop_stack();
                 rt.globals.vars.insert(Value::from_uint(g), stack_value);
             }
-            OpCode::SendMessage { id, arg_count } => {
-                let type_id = rt.peek_at(arg_count).type_of();
-                let ty = rt
-                    .globals
-                    .types
-                    .get(&Value::from_uint(type_id as u64))
-                    .unwrap_or_else(|| panic!("Type {} doesn't exist", type_id as u64));
-                let ty_map = unsafe {
-                    HeapMap::from_ptr(
-                        NonNull::new(ty.as_ptr() as *mut Value).unwrap(),
-                        &mut rt.heap,
-                    )
-                };
-                let handler = ty_map.get(&Value::from_uint(id)).unwrap_or_else(|| {
-                    panic!(
-                        "Failed to get handler with id {id}: {:#?}",
-                        rt.globals.idents
-                    )
-                });
-                let res = unsafe {
-                    std::mem::transmute::<usize, ExternHandler>(handler.as_uint() as usize)
-                }(rt, arg_count);
-                if let Some(output) = res {
+            OpCode::StoreGlobal(g) => {
+                let stack_value = *rt.peek();
+                rt.globals.vars.insert(Value::from_uint(g), stack_value);
+            }
+            OpCode::SendMessage {
+                id,
+                arg_count,
+                cache,
+            } => send_op::<false>(rt, consts, site, id, arg_count, cache),
+            OpCode::SendUnary0 { id, cache } => send_op::<false>(rt, consts, site, id, 0, cache),
+            OpCode::SendBinary { id, cache } => send_op::<false>(rt, consts, site, id, 1, cache),
+            OpCode::TailSend {
+                id,
+                arg_count,
+                cache,
+            } => send_op::<true>(rt, consts, site, id, arg_count, cache),
+            OpCode::AddInt { cache } => {
+                let [lhs, rhs] = rt.top_n::<2>();
+                if rt.globals.int_fast_path
+                    && lhs.is_int()
+                    && rhs.is_int()
+                    && let Some(sum) = lhs.as_int().checked_add(rhs.as_int())
+                {
+                    rt.replace_top_n::<2>(Value::from_int(sum));
+                } else {
+                    send_op::<false>(rt, consts, site, IdentArena::ADD, 1, cache);
+                }
+            }
+            OpCode::LessInt { cache } => {
+                let [lhs, rhs] = rt.top_n::<2>();
+                if rt.globals.int_fast_path && lhs.is_int() && rhs.is_int() {
+                    rt.replace_top_n::<2>(Value::from_bool(lhs.as_int() < rhs.as_int()));
+                } else {
+                    send_op::<false>(rt, consts, site, IdentArena::LESS, 1, cache);
+                }
+            }
+            OpCode::PushSelf => {
+                let frame = rt.frames.last().expect("super used outside of a handler");
+                rt.push_stack(frame.receiver);
+            }
+            OpCode::SuperSend { id, arg_count } => {
+                rt.site = site;
+                if let Some(output) = send_super(rt, id, arg_count) {
                     rt.push_stack(output);
                 }
             }
+            OpCode::BuildString(n) => {
+                let parts = rt.stack.split_off(rt.stack.len() - n as usize);
+                let mut s = String::new();
+                for part in parts {
+                    if part.type_of() == TypeId::String {
+                        s.push_str(as_string(part));
+                    } else {
+                        s.push_str(display_string(rt, part));
+                    }
+                }
+                let s = Value::from_str(&s, &mut rt.heap);
+                rt.push_stack(s);
+            }
         }
     }
 }
+
+#[inline(always)]
+fn send_op<const TAIL: bool>(
+    rt: &mut Runtime,
+    consts: &[Value],
+    site: SendSite,
+    id: u64,
+    arg_count: u64,
+    cache: u64,
+) {
+    rt.site = site;
+    let cache = consts[cache as usize].as_ptr() as *mut InlineCache;
+    let cache = NonNull::new(cache).expect("Inline cache should not be null");
+    let output = if TAIL {
+        send_cached_tail(rt, id, arg_count, cache)
+    } else {
+        send_cached(rt, id, arg_count, cache)
+    };
+    if let Some(output) = output {
+        rt.push_stack(output);
+    }
+}
+
+/// Dispatches `id` to the receiver sitting below `arg_count` arguments on the stack.
+pub fn send_message(rt: &mut Runtime, id: u64, arg_count: u64) -> Option<Value> {
+    let type_id = rt.peek_at(arg_count).type_of();
+    dispatch(rt, type_id, id, arg_count)
+}
+
+/// Like `send_message`, but the lookup starts above the type holding the running handler.
+pub fn send_super(rt: &mut Runtime, id: u64, arg_count: u64) -> Option<Value> {
+    let frame = rt.frames.last().expect("super used outside of a handler");
+    let holder = frame.holder;
+    let start = rt
+        .superclass_of(holder)
+        .unwrap_or_else(|| panic!("{} has no superclass", rt.type_name(holder)));
+    dispatch(rt, start, id, arg_count)
+}
+
+/// Like `send_message`, but consults and fills the call site's inline cache first.
+pub fn send_cached(
+    rt: &mut Runtime,
+    id: u64,
+    arg_count: u64,
+    cache: NonNull<InlineCache>,
+) -> Option<Value> {
+    let (handler, holder) = lookup_cached(rt, id, arg_count, cache);
+    invoke::<false>(rt, handler, holder, id, arg_count)
+}
+
+/// Like `send_cached`, but for a send whose value is that of the block making it. A handler
+/// written in Howl is left in `rt.tail_call` for the `call_handler` below to run in place of
+/// the current one.
+pub fn send_cached_tail(
+    rt: &mut Runtime,
+    id: u64,
+    arg_count: u64,
+    cache: NonNull<InlineCache>,
+) -> Option<Value> {
+    let (handler, holder) = lookup_cached(rt, id, arg_count, cache);
+    if rt.config.no_tail_calls {
+        invoke::<false>(rt, handler, holder, id, arg_count)
+    } else {
+        invoke::<true>(rt, handler, holder, id, arg_count)
+    }
+}
+
+fn lookup_cached(
+    rt: &mut Runtime,
+    id: u64,
+    arg_count: u64,
+    cache: NonNull<InlineCache>,
+) -> (Value, TypeId) {
+    let type_id = rt.peek_at(arg_count).type_of();
+    let cache = unsafe { &mut *cache.as_ptr() };
+    match cache.get(type_id, rt.globals.handler_epoch) {
+        Some(hit) => hit,
+        None => {
+            let (handler, holder) = lookup(rt, type_id, id, arg_count);
+            cache.insert(type_id, handler, holder);
+            (handler, holder)
+        }
+    }
+}
+
+fn dispatch(rt: &mut Runtime, start: TypeId, id: u64, arg_count: u64) -> Option<Value> {
+    let (handler, holder) = lookup(rt, start, id, arg_count);
+    invoke::<false>(rt, handler, holder, id, arg_count)
+}
+
+fn lookup(rt: &mut Runtime, start: TypeId, id: u64, arg_count: u64) -> (Value, TypeId) {
+    rt.lookup_handler(start, id).unwrap_or_else(|| {
+        let receiver = rt.peek_at(arg_count).type_of();
+        let selector = rt.globals.idents.get(id).unwrap_or_default();
+        let message = format!("{} does not understand #{selector}", rt.type_name(receiver));
+        raise(TypeId::MessageNotUnderstood, message)
+    })
+}
+
+/// Runs `handler` on the receiver and arguments at the top of the stack. With `TAIL`, a
+/// pending tail call is passed on to the caller, otherwise it is run before answering.
+fn invoke<const TAIL: bool>(
+    rt: &mut Runtime,
+    handler: Value,
+    holder: TypeId,
+    selector: u64,
+    arg_count: u64,
+) -> Option<Value> {
+    if handler.is_ptr() {
+        // A Block installed from Howl, taking the receiver as its first argument
+        let args = rt.stack.split_off(rt.stack.len() - arg_count as usize - 1);
+        if TAIL {
+            rt.tail_call = Some(TailCall {
+                handler,
+                holder,
+                selector,
+                args,
+            });
+            return None;
+        }
+        call_handler(rt, handler, holder, selector, args)
+    } else {
+        let handler = unsafe { mem::transmute::<usize, ExternHandler>(handler.as_uint() as usize) };
+        let result = handler(rt, arg_count);
+        if !TAIL && rt.tail_call.is_some() {
+            return run_tail_call(rt);
+        }
+        result
+    }
+}
+
+/// A handler written in Howl waiting to run in place of the one that sent to it in tail
+/// position.
+#[derive(Debug)]
+pub struct TailCall {
+    handler: Value,
+    holder: TypeId,
+    selector: u64,
+    /// The receiver followed by the arguments
+    args: Vec<Value>,
+}
+
+/// Runs a handler written in Howl in a frame of its own, reusing the frame for every tail call
+/// it and its successors make.
+fn call_handler(
+    rt: &mut Runtime,
+    mut handler: Value,
+    mut holder: TypeId,
+    mut selector: u64,
+    mut args: Vec<Value>,
+) -> Option<Value> {
+    loop {
+        rt.frames.push(Frame {
+            receiver: args[0],
+            selector,
+            holder,
+        });
+        let result = unsafe { call_block_in_tail(handler.as_ptr() as *const u8, rt, &args) };
+        rt.frames.pop();
+        match rt.tail_call.take() {
+            Some(call) => {
+                (handler, holder, selector, args) =
+                    (call.handler, call.holder, call.selector, call.args)
+            }
+            None => return result,
+        }
+    }
+}
+
+/// Runs the tail call left in `rt.tail_call`, if any, answering its value.
+pub fn run_tail_call(rt: &mut Runtime) -> Option<Value> {
+    let call = rt.tail_call.take()?;
+    call_handler(rt, call.handler, call.holder, call.selector, call.args)
+}
@@ -0,0 +1,72 @@
+use crate::vm::{
+    runtime::Heap,
+    value::{TypeId, Value},
+};
+use std::ptr::NonNull;
+
+/// How many receiver types a call site remembers before it stops caching new ones.
+const POLYMORPHIC_LIMIT: usize = 4;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct CacheEntry {
+    type_id: TypeId,
+    handler: Value,
+    holder: TypeId,
+}
+
+/// A per-call-site cache from receiver type to the handler a lookup found for it. Each cache
+/// remembers the handler epoch it was filled in; any change to a handler map bumps the
+/// epoch, emptying every cache on its next use.
+#[repr(C)]
+pub struct InlineCache {
+    epoch: u64,
+    len: u64,
+    entries: [CacheEntry; POLYMORPHIC_LIMIT],
+}
+
+impl InlineCache {
+    /// Allocates an empty cache for a call site being compiled.
+    pub fn alloc(heap: &mut Heap) -> NonNull<InlineCache> {
+        let ptr = heap
+            .alloc(size_of::<InlineCache>() as u64, TypeId::NONE)
+            .cast::<InlineCache>();
+        let empty = CacheEntry {
+            type_id: TypeId::NONE,
+            handler: Value::nil(),
+            holder: TypeId::NONE,
+        };
+        unsafe {
+            ptr.write(InlineCache {
+                epoch: 0,
+                len: 0,
+                entries: [empty; POLYMORPHIC_LIMIT],
+            })
+        };
+        ptr
+    }
+
+    pub fn get(&mut self, type_id: TypeId, epoch: u64) -> Option<(Value, TypeId)> {
+        if self.epoch != epoch {
+            self.epoch = epoch;
+            self.len = 0;
+            return None;
+        }
+        self.entries[..self.len as usize]
+            .iter()
+            .find(|entry| entry.type_id == type_id)
+            .map(|entry| (entry.handler, entry.holder))
+    }
+
+    /// Remembers a lookup result, unless the call site is already megamorphic.
+    pub fn insert(&mut self, type_id: TypeId, handler: Value, holder: TypeId) {
+        if let Some(entry) = self.entries.get_mut(self.len as usize) {
+            *entry = CacheEntry {
+                type_id,
+                handler,
+                holder,
+            };
+            self.len += 1;
+        }
+    }
+}
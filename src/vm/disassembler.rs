@@ -0,0 +1,158 @@
+use crate::{
+    source_line,
+    std::{large_int::as_bigint, object::article, string::as_string},
+    vm::{
+        bytecode::{Chunk, LineEntry, OpCode, block_lines, block_parts, decode_all},
+        runtime::{Runtime, as_type_object},
+        value::{TypeId, Value},
+    },
+};
+use std::fmt::Write;
+
+/// Renders `chunk` one instruction per line, resolving identifiers and constants through `rt`.
+/// Block constants are disassembled in place, indented under the instruction that pushes them.
+/// When the `source` the chunk was compiled from is given, each statement is preceded by its
+/// line number and text.
+pub fn disassemble(rt: &Runtime, chunk: &Chunk, source: Option<&str>) -> String {
+    let mut out = String::new();
+    write_code(
+        &mut out,
+        rt,
+        &chunk.consts,
+        &chunk.code,
+        &chunk.lines,
+        source,
+        0,
+    );
+    out
+}
+
+fn write_code(
+    out: &mut String,
+    rt: &Runtime,
+    consts: &[Value],
+    code: &[u8],
+    lines: &[LineEntry],
+    source: Option<&str>,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    let mut last_line = None;
+    for (offset, op) in decode_all(code) {
+        if let Some(source) = source
+            && let Some(entry) = lines.iter().find(|e| e.code_offset as usize == offset)
+        {
+            // Statements sharing a line are only annotated once
+            let (line, text) = source_line(source, entry.source_offset as usize);
+            if last_line != Some(line) {
+                writeln!(out, "{indent}; line {line}: {text}").unwrap();
+                last_line = Some(line);
+            }
+        }
+
+        let (text, comment) = describe_op(rt, consts, op);
+        match comment {
+            Some(comment) => writeln!(out, "{indent}{offset:04}  {text:<28}; {comment}"),
+            None => writeln!(out, "{indent}{offset:04}  {text}"),
+        }
+        .unwrap();
+
+        if let OpCode::PushConst(i) = op
+            && consts[i as usize].type_of() == TypeId::CompiledBytecode
+        {
+            let ptr = consts[i as usize].as_ptr() as *const u8;
+            let (_, block_consts, block_code) = unsafe { block_parts(ptr) };
+            let block_lines = unsafe { block_lines(ptr) };
+            write_code(
+                out,
+                rt,
+                block_consts,
+                block_code,
+                block_lines,
+                source,
+                depth + 1,
+            );
+        }
+    }
+}
+
+/// Answers the instruction's text and, for operands that index into something, what they
+/// refer to.
+fn describe_op(rt: &Runtime, consts: &[Value], op: OpCode) -> (String, Option<String>) {
+    match op {
+        OpCode::PushConst(i) => (
+            format!("PushConst {i}"),
+            Some(describe_value(rt, consts[i as usize])),
+        ),
+        OpCode::PushSmallInt(i) => (format!("PushSmallInt {i}"), None),
+        OpCode::PushGlobal(g) => (format!("PushGlobal {}", ident(rt, g)), None),
+        OpCode::SetGlobal(g) => (format!("SetGlobal {}", ident(rt, g)), None),
+        OpCode::StoreGlobal(g) => (format!("StoreGlobal {}", ident(rt, g)), None),
+        OpCode::SendMessage {
+            id,
+            arg_count,
+            cache,
+        } => (
+            format!("SendMessage #{} {arg_count}", ident(rt, id)),
+            Some(format!("cache {cache}")),
+        ),
+        OpCode::AddInt { cache } => ("AddInt".to_string(), Some(format!("cache {cache}"))),
+        OpCode::LessInt { cache } => ("LessInt".to_string(), Some(format!("cache {cache}"))),
+        OpCode::SendUnary0 { id, cache } => (
+            format!("SendUnary0 #{}", ident(rt, id)),
+            Some(format!("cache {cache}")),
+        ),
+        OpCode::SendBinary { id, cache } => (
+            format!("SendBinary #{}", ident(rt, id)),
+            Some(format!("cache {cache}")),
+        ),
+        OpCode::TailSend {
+            id,
+            arg_count,
+            cache,
+        } => (
+            format!("TailSend #{} {arg_count}", ident(rt, id)),
+            Some(format!("cache {cache}")),
+        ),
+        OpCode::BuildString(n) => (format!("BuildString {n}"), None),
+        OpCode::PushSelf => ("PushSelf".to_string(), None),
+        OpCode::SuperSend { id, arg_count } => {
+            (format!("SuperSend #{} {arg_count}", ident(rt, id)), None)
+        }
+    }
+}
+
+fn ident(rt: &Runtime, id: u64) -> String {
+    match rt.globals.idents.get(id) {
+        Some(name) => name.to_string(),
+        None => format!("<ident {id}>"),
+    }
+}
+
+/// Describes a constant the way it would be written in source where possible. This never sends
+/// messages, so it is safe to call on any runtime state.
+fn describe_value(rt: &Runtime, v: Value) -> String {
+    match v.type_of() {
+        TypeId::Int => v.as_int().to_string(),
+        TypeId::Float => format!("{:?}", v.as_float()),
+        TypeId::Character => format!("${}", v.as_char()),
+        TypeId::String => format!("{:?}", as_string(v)),
+        TypeId::Symbol => format!("#{}", as_string(v)),
+        TypeId::LargeInt => as_bigint(v).to_string(),
+        TypeId::Nil => "Nil".to_string(),
+        TypeId::True => "True".to_string(),
+        TypeId::False => "False".to_string(),
+        TypeId::Type => as_string(as_type_object(v).name).to_string(),
+        TypeId::CompiledBytecode => {
+            let (header, _, _) = unsafe { block_parts(v.as_ptr() as *const u8) };
+            match header.param_count {
+                1 => "a Block (1 param)".to_string(),
+                n => format!("a Block ({n} params)"),
+            }
+        }
+        id => {
+            let name = rt.type_name(id);
+            format!("{} {name}", article(name))
+        }
+    }
+}
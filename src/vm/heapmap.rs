@@ -1,27 +1,70 @@
 use crate::vm::{
-    runtime::{Heap, HeapAllocator},
-    value::Value,
+    runtime::Heap,
+    value::{TypeId, Value},
 };
-use hashbrown::raw::RawTable;
+use hashbrown::HashMap;
 use std::ptr::NonNull;
 
+/// The heap object a `HeapMap` points at. The table's buckets live outside the heap, which
+/// drops them along with itself, so they grow without counting against the heap's capacity.
 #[repr(C, align(16))]
 pub struct HeapMapHeader {
-    capacity: u64,
-    count: u64,
-    ptr: NonNull<u8>,
+    table: HashMap<Value, Value>,
 }
 
+/// A handle to a map stored on the heap, so that it can be kept in a Value, e.g. as the
+/// handlers of a type object.
+#[derive(Clone, Copy, Debug)]
 pub struct HeapMap {
-    inner_map: HashMap<Value, Value>,
-    allocator: HeapAllocator<HeapMapHeader>,
+    pub ptr: NonNull<HeapMapHeader>,
 }
 
 impl HeapMap {
     pub fn new(heap: &mut Heap, capacity: u64) -> Self {
-        let allocator = HeapAllocator::new(heap);
-        let map = HashTable::with_capacity_in(capacity as usize, allocator);
+        let ptr = heap
+            .alloc(size_of::<HeapMapHeader>() as u64, TypeId::HeapMap)
+            .cast::<HeapMapHeader>();
+        unsafe {
+            ptr.write(HeapMapHeader {
+                table: HashMap::with_capacity(capacity as usize),
+            })
+        };
+        heap.track_map(ptr);
+        Self { ptr }
+    }
+
+    /// # Safety
+    /// `ptr` must point at a map created by `HeapMap::new` on `heap`.
+    pub unsafe fn from_ptr(ptr: NonNull<Value>, _heap: &mut Heap) -> Self {
+        Self { ptr: ptr.cast() }
+    }
+
+    pub fn get(&self, key: &Value) -> Option<Value> {
+        self.table().get(key).copied()
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) {
+        self.table_mut().insert(key, value);
+    }
+
+    /// Keeps only the entries `keep` answers true for.
+    pub fn retain(&mut self, mut keep: impl FnMut(Value, Value) -> bool) {
+        self.table_mut().retain(|&key, &mut value| keep(key, value));
+    }
+
+    pub fn len(&self) -> usize {
+        self.table().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+
+    fn table(&self) -> &HashMap<Value, Value> {
+        unsafe { &self.ptr.as_ref().table }
+    }
 
-        let map_ptr = map.raw;
+    fn table_mut(&mut self) -> &mut HashMap<Value, Value> {
+        unsafe { &mut self.ptr.as_mut().table }
     }
 }
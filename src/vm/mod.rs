@@ -1,4 +1,11 @@
+pub mod bigint;
+pub mod budget;
+pub mod builder;
 pub mod bytecode;
+pub mod cache;
+pub mod disassembler;
 pub mod heapmap;
+pub mod optimizer;
+pub mod output;
 pub mod runtime;
 pub mod value;
@@ -0,0 +1,365 @@
+use crate::{
+    std::{number::is_number, string::as_string},
+    vm::{
+        bytecode::{Chunk, OpCode, block_chunk, decode_all, send_message},
+        runtime::Runtime,
+        value::{TypeId, Value},
+    },
+};
+
+/// How much `optimize` rewrites a chunk before it runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// Code runs exactly as it was compiled
+    #[default]
+    None,
+    /// Rewrites that never change what a program does: `SetGlobal x; PushGlobal x` becomes
+    /// `StoreGlobal x`, and literal statements whose value is never used are dropped
+    Peephole,
+    /// Also evaluates sends of pure std handlers to literals ahead of time. This assumes those
+    /// handlers aren't redefined once code using them has been compiled.
+    Fold,
+}
+
+/// Selectors whose std handlers answer a fresh value without side effects when both the
+/// receiver and the arguments are numbers.
+const PURE_NUMBER: &[&str] = &[
+    "+",
+    "-",
+    "*",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "==",
+    "!=",
+    "max:",
+    "min:",
+    "abs",
+    "negated",
+    "isEven",
+    "isOdd",
+    "bitInvert",
+    "asFloat",
+    "floor",
+    "ceiling",
+    "rounded",
+    "truncated",
+    "sqrt",
+];
+
+/// Like `PURE_NUMBER`, but these fail when dividing an integer by zero.
+const PURE_DIVISION: &[&str] = &["/", "div:", "mod:", "\\\\", "rem:"];
+
+/// Pure selectors of Characters, when every argument is a Character too.
+const PURE_CHARACTER: &[&str] = &[
+    "value",
+    "isLetter",
+    "isDigit",
+    "isVowel",
+    "isUppercase",
+    "isLowercase",
+    "isWhitespace",
+    "asUppercase",
+    "asLowercase",
+    "==",
+    "!=",
+    "<",
+    ">",
+];
+
+#[derive(Clone, Copy)]
+struct Instr {
+    op: OpCode,
+    /// Set on the first instruction of each statement, see `Chunk::mark_source`
+    source: Option<u32>,
+}
+
+/// Rewrites `chunk` and every block it creates according to `rt.config.opt_level`.
+///
+/// Howl has no non-local return, so every statement in a chunk is reachable and there is no
+/// dead code to remove after one.
+pub fn optimize(chunk: &mut Chunk, rt: &mut Runtime) {
+    let level = rt.config.opt_level;
+    if level == OptLevel::None {
+        return;
+    }
+    // Code that installs handlers could change what a folded send would have answered
+    let fold = level >= OptLevel::Fold && !installs_handlers(chunk, rt);
+    optimize_chunk(chunk, rt, fold);
+}
+
+fn optimize_chunk(chunk: &mut Chunk, rt: &mut Runtime, fold: bool) {
+    let mut instrs = decode(chunk);
+
+    for instr in &instrs {
+        if let OpCode::PushConst(i) = instr.op {
+            let block = chunk.consts[i as usize];
+            if block.type_of() == TypeId::CompiledBytecode {
+                let (mut body, param_count) = unsafe { block_chunk(block.as_ptr() as *const u8) };
+                optimize_chunk(&mut body, rt, fold);
+                chunk.consts[i as usize] = body.alloc_block(param_count, &mut rt.heap);
+            }
+        }
+    }
+
+    if fold {
+        fold_constants(&mut instrs, chunk, rt);
+    }
+    remove_unused_literals(&mut instrs);
+    merge_stores(&mut instrs);
+    *chunk = encode(&instrs, &chunk.consts);
+}
+
+fn decode(chunk: &Chunk) -> Vec<Instr> {
+    let mut lines = chunk.lines.iter().peekable();
+    decode_all(&chunk.code)
+        .into_iter()
+        .map(|(offset, op)| {
+            let source = lines
+                .next_if(|entry| entry.code_offset as usize == offset)
+                .map(|entry| entry.source_offset);
+            Instr { op, source }
+        })
+        .collect()
+}
+
+/// Re-encodes `instrs`, keeping only the constants they still use.
+fn encode(instrs: &[Instr], consts: &[Value]) -> Chunk {
+    let mut used = vec![false; consts.len()];
+    for instr in instrs {
+        match instr.op {
+            OpCode::PushConst(i) => used[i as usize] = true,
+            op => {
+                if let Some((_, _, cache)) = op.as_send() {
+                    used[cache as usize] = true;
+                }
+            }
+        }
+    }
+    let mut remap = vec![0; consts.len()];
+    let mut chunk = Chunk::default();
+    for (i, &c) in consts.iter().enumerate() {
+        if used[i] {
+            remap[i] = chunk.consts.len() as u64;
+            chunk.consts.push(c);
+        }
+    }
+
+    for instr in instrs {
+        if let Some(source) = instr.source {
+            chunk.mark_source(source as usize);
+        }
+        let op = match instr.op {
+            OpCode::PushConst(i) => OpCode::PushConst(remap[i as usize]),
+            OpCode::SendMessage {
+                id,
+                arg_count,
+                cache,
+            } => OpCode::SendMessage {
+                id,
+                arg_count,
+                cache: remap[cache as usize],
+            },
+            OpCode::AddInt { cache } => OpCode::AddInt {
+                cache: remap[cache as usize],
+            },
+            OpCode::LessInt { cache } => OpCode::LessInt {
+                cache: remap[cache as usize],
+            },
+            OpCode::SendUnary0 { id, cache } => OpCode::SendUnary0 {
+                id,
+                cache: remap[cache as usize],
+            },
+            OpCode::SendBinary { id, cache } => OpCode::SendBinary {
+                id,
+                cache: remap[cache as usize],
+            },
+            OpCode::TailSend {
+                id,
+                arg_count,
+                cache,
+            } => OpCode::TailSend {
+                id,
+                arg_count,
+                cache: remap[cache as usize],
+            },
+            op => op,
+        };
+        chunk.emit(op);
+    }
+    chunk
+}
+
+/// Replaces sends of pure std handlers to literals, and interpolations of nothing but literal
+/// Strings, with their result.
+fn fold_constants(instrs: &mut Vec<Instr>, chunk: &mut Chunk, rt: &mut Runtime) {
+    let mut i = 0;
+    while i < instrs.len() {
+        let op = instrs[i].op;
+        let (operand_count, folded) = match (op, op.as_send()) {
+            (_, Some((id, arg_count, _))) => {
+                let n = arg_count as usize + 1;
+                let operands = literal_operands(instrs, chunk, i, n);
+                (
+                    n,
+                    operands.and_then(|operands| fold_send(rt, id, &operands)),
+                )
+            }
+            (OpCode::BuildString(n), _) => {
+                let n = n as usize;
+                let operands = literal_operands(instrs, chunk, i, n);
+                (
+                    n,
+                    operands.and_then(|operands| fold_interpolation(rt, &operands)),
+                )
+            }
+            _ => (0, None),
+        };
+
+        match folded {
+            Some(value) => {
+                let start = i - operand_count;
+                let op = match value.is_int() {
+                    true => OpCode::PushSmallInt(value.as_int()),
+                    false => OpCode::PushConst(chunk.add_const(value)),
+                };
+                let folded = Instr {
+                    op,
+                    source: instrs[start].source,
+                };
+                instrs.splice(start..=i, [folded]);
+                i = start + 1;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// The values pushed by the `n` instructions before `end`, if they are all constants within
+/// the same statement as `end`.
+fn literal_operands(instrs: &[Instr], chunk: &Chunk, end: usize, n: usize) -> Option<Vec<Value>> {
+    let start = end.checked_sub(n)?;
+    if instrs[start + 1..=end].iter().any(|i| i.source.is_some()) {
+        return None;
+    }
+    instrs[start..end]
+        .iter()
+        .map(|instr| match instr.op {
+            OpCode::PushConst(c) => Some(chunk.consts[c as usize]),
+            OpCode::PushSmallInt(i) => Some(Value::from_int(i)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn fold_send(rt: &mut Runtime, id: u64, operands: &[Value]) -> Option<Value> {
+    let selector = rt.globals.idents.get(id)?;
+    let (&receiver, args) = operands.split_first()?;
+    if args.len() != arity(&selector) || !is_pure(&selector, receiver, args) {
+        return None;
+    }
+    // Only the std handlers are known to be pure, not ones defined in Howl
+    let (handler, _) = rt.lookup_handler(receiver.type_of(), id)?;
+    if handler.is_ptr() {
+        return None;
+    }
+    rt.push_stack(receiver);
+    rt.stack.extend_from_slice(args);
+    send_message(rt, id, args.len() as u64)
+}
+
+fn fold_interpolation(rt: &mut Runtime, operands: &[Value]) -> Option<Value> {
+    let mut s = String::new();
+    for &part in operands {
+        if part.type_of() != TypeId::String {
+            return None;
+        }
+        s.push_str(as_string(part));
+    }
+    Some(Value::from_str(&s, &mut rt.heap))
+}
+
+fn is_pure(selector: &str, receiver: Value, args: &[Value]) -> bool {
+    if is_number(receiver) && args.iter().all(|&a| is_number(a)) {
+        let divides_by_zero = args.iter().any(|&a| a == Value::from_int(0));
+        PURE_NUMBER.contains(&selector) || PURE_DIVISION.contains(&selector) && !divides_by_zero
+    } else if receiver.is_char() && args.iter().all(|a| a.is_char()) {
+        PURE_CHARACTER.contains(&selector)
+    } else {
+        false
+    }
+}
+
+/// How many arguments `selector` takes when sent as written, e.g. `at:put:` takes 2.
+pub fn arity(selector: &str) -> usize {
+    if selector.ends_with(':') {
+        selector.matches(':').count()
+    } else if selector.starts_with(|c: char| c.is_alphabetic()) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Drops statements consisting of a single literal, unless no later statement pushes a value
+/// and so the literal provides the chunk's result.
+fn remove_unused_literals(instrs: &mut Vec<Instr>) {
+    let starts: Vec<usize> = (0..instrs.len())
+        .filter(|&i| instrs[i].source.is_some())
+        .collect();
+    let mut later_pushes = false;
+    for (n, &start) in starts.iter().enumerate().rev() {
+        let end = starts.get(n + 1).copied().unwrap_or(instrs.len());
+        let literal = matches!(
+            instrs[start].op,
+            OpCode::PushConst(_) | OpCode::PushSmallInt(_)
+        );
+        if literal && end == start + 1 && later_pushes {
+            instrs.remove(start);
+        } else if !matches!(instrs[end - 1].op, OpCode::SetGlobal(_)) {
+            // Only assignments leave nothing behind
+            later_pushes = true;
+        }
+    }
+}
+
+/// Turns `SetGlobal x; PushGlobal x` into `StoreGlobal x`.
+fn merge_stores(instrs: &mut Vec<Instr>) {
+    let mut i = 0;
+    while i + 1 < instrs.len() {
+        match (instrs[i].op, instrs[i + 1].op) {
+            (OpCode::SetGlobal(set), OpCode::PushGlobal(push)) if set == push => {
+                instrs[i].op = OpCode::StoreGlobal(set);
+                let removed = instrs.remove(i + 1);
+                // Whatever followed the push now starts its statement
+                if let Some(next) = instrs.get_mut(i + 1)
+                    && next.source.is_none()
+                {
+                    next.source = removed.source;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// Whether `chunk` or any block in it sends `instanceMessage:handler:`.
+fn installs_handlers(chunk: &Chunk, rt: &Runtime) -> bool {
+    let Some(selector) = rt.globals.idents.find("instanceMessage:handler:") else {
+        return false;
+    };
+    sends(&chunk.code, &chunk.consts, selector)
+}
+
+fn sends(code: &[u8], consts: &[Value], selector: u64) -> bool {
+    decode_all(code).into_iter().any(|(_, op)| match op {
+        OpCode::SuperSend { id, .. } => id == selector,
+        OpCode::PushConst(i) if consts[i as usize].type_of() == TypeId::CompiledBytecode => {
+            let (block, _) = unsafe { block_chunk(consts[i as usize].as_ptr() as *const u8) };
+            sends(&block.code, &block.consts, selector)
+        }
+        op => op.as_send().is_some_and(|(id, ..)| id == selector),
+    })
+}
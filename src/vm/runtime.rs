@@ -1,13 +1,18 @@
 use crate::{
     IdentArena,
+    std::{error::raise, string::as_string},
     vm::{
-        bytecode::OpCode,
-        heapmap::HeapMap,
+        budget::Budget,
+        builder::RuntimeBuilder,
+        bytecode::{Chunk, OpCode, SendSite, TailCall, send_message},
+        heapmap::{HeapMap, HeapMapHeader},
+        optimizer::OptLevel,
         value::{TypeId, Value},
     },
 };
 use std::{
-    alloc::{AllocError, Allocator, Layout},
+    alloc::{AllocError, Allocator, Layout, alloc, dealloc},
+    io::{self, Write},
     marker::PhantomData,
     mem::MaybeUninit,
     ptr::NonNull,
@@ -15,48 +20,203 @@ use std::{
 
 pub struct Runtime {
     pub stack: Vec<Value>,
-    pub code: Vec<OpCode>,
+    pub code: Chunk,
     pub pc: u64,
     pub heap: Heap,
     pub globals: Globals,
+    /// One frame per running Howl-defined handler
+    pub frames: Vec<Frame>,
+    /// How many blocks are running inside each other, handlers included
+    pub depth: usize,
+    /// Where the latest send from bytecode was made, for pointing errors at their source
+    pub site: SendSite,
+    /// A send in tail position waiting to run in place of the handler that made it
+    pub tail_call: Option<TailCall>,
+    pub exceptions: Exceptions,
+    /// Limits on how much more code may run
+    pub budget: Budget,
+    pub config: Config,
+    /// Where `display`, `>>` and `Transcript` write; stdout unless replaced
+    pub out: Box<dyn Write>,
+    /// Where diagnostics and traces are written; stderr unless replaced
+    pub err: Box<dyn Write>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Dump the compiled bytecode to `err` before running it
+    pub trace: bool,
+    /// How much the optimizer rewrites compiled code before it runs
+    pub opt_level: OptLevel,
+    /// Run sends in tail position on top of the handler making them instead of in its place,
+    /// keeping every handler in `frames` at the cost of stack depth
+    pub no_tail_calls: bool,
+    /// How many blocks may run inside each other before a `StackOverflow` is raised. Each one
+    /// takes native stack, so this shouldn't be raised far past the default without running on
+    /// a thread with a larger stack.
+    pub max_depth: usize,
+    /// How many values the operand stack may hold before a `StackOverflow` is raised
+    pub max_stack: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            trace: false,
+            opt_level: OptLevel::default(),
+            no_tail_calls: false,
+            max_depth: 1_000,
+            max_stack: 1 << 20,
+        }
+    }
 }
 
 pub struct Globals {
     pub idents: IdentArena,
     /// id -> val
     pub vars: HeapMap,
-    /// id -> <id -> Method>
+    /// type id -> TypeObject
     pub types: HeapMap,
+    next_type_id: u64,
+    /// Bumped whenever a handler map changes, invalidating every inline cache
+    pub handler_epoch: u64,
+    /// Whether `Int>>+` and `Int>><` are still the std handlers, letting `AddInt` and `LessInt`
+    /// skip the send
+    pub int_fast_path: bool,
+}
+
+/// The heap layout of a type, which is also the value Howl code sees for it (e.g. `Int`).
+#[repr(C)]
+pub struct TypeObject {
+    pub id: TypeId,
+    /// selector id -> handler, either an extern fn pointer (an Int) or a Block
+    pub handlers: Value,
+    /// The next type to search when a handler isn't found here, or Nil for `Object`
+    pub superclass: Value,
+    pub name: Value,
+}
+
+/// Tracks a Howl-defined handler while it runs, so that `super` knows where to continue the
+/// lookup from and backtraces can tell what was running.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub receiver: Value,
+    /// The selector of the message that ran the handler
+    pub selector: u64,
+    /// The type whose handler map the running handler was found in
+    pub holder: TypeId,
+}
+
+/// An `on:do:` waiting for an error of `error_type`, or one of its subtypes, to be signaled.
+#[derive(Clone, Copy, Debug)]
+pub struct ExceptionHandler {
+    pub id: u64,
+    pub error_type: TypeId,
+    /// The Block handling the error, or Nil when native code catches it instead
+    pub block: Value,
+}
+
+/// An error whose handler is running, so that `retry`, `return:` and `resume:` know where to
+/// continue.
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveSignal {
+    pub error: Value,
+    /// The id of the handler handling it
+    pub handler: u64,
+    /// Whether it was signaled rather than raised natively, leaving a `signal` to resume
+    pub resumable: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Exceptions {
+    /// Innermost last
+    pub handlers: Vec<ExceptionHandler>,
+    /// Innermost last
+    pub signals: Vec<ActiveSignal>,
+    next_id: u64,
+}
+
+impl Exceptions {
+    pub fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+/// How deep the runtime's stacks were at some point, so that they can be cut back to it once an
+/// error unwinds past whatever was pushed since.
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    stack: usize,
+    frames: usize,
+    depth: usize,
+    handlers: usize,
+    signals: usize,
 }
 
 type ExternHandler = fn(rt: &mut Runtime, arg_count: u64) -> Option<Value>;
 
+/// How many handlers a `StackOverflow` lists; the rest are usually more of the same.
+const BACKTRACE_LIMIT: usize = 10;
+
 impl Default for Runtime {
     fn default() -> Self {
-        let mut heap = Heap::default();
+        Self::new(Config::default())
+    }
+}
+
+impl Runtime {
+    /// A runtime with every std type and handler, see `RuntimeBuilder` for fewer.
+    pub fn new(config: Config) -> Self {
+        RuntimeBuilder::new().config(config).build()
+    }
+
+    /// A runtime without any std types, for `RuntimeBuilder` to fill in.
+    pub(crate) fn empty(config: Config, mut heap: Heap) -> Self {
         let globals = Globals {
             idents: IdentArena::default(),
             vars: HeapMap::new(&mut heap, 4_096),
             types: HeapMap::new(&mut heap, 64),
+            next_type_id: TypeId::FIRST_USER.0,
+            handler_epoch: 0,
+            int_fast_path: false,
         };
-        let mut rt = Self {
+        Self {
             heap,
             globals,
             pc: 0,
             stack: Vec::with_capacity(30),
-            code: Vec::new(),
-        };
-        crate::std::define_std_types(&mut rt);
-        rt
+            code: Chunk::default(),
+            frames: Vec::new(),
+            depth: 0,
+            site: SendSite::default(),
+            tail_call: None,
+            exceptions: Exceptions::default(),
+            budget: Budget::default(),
+            config,
+            out: Box::new(io::stdout()),
+            err: Box::new(io::stderr()),
+        }
     }
 }
 
 impl Runtime {
     #[inline(always)]
     pub fn push_stack(&mut self, v: Value) {
+        if self.stack.len() >= self.config.max_stack {
+            let max = self.config.max_stack;
+            self.stack_overflow(format!("Operand stack grew past {max} values"));
+        }
         self.stack.push(v);
     }
 
+    /// Raises a `StackOverflow` saying which limit was hit, followed by a backtrace.
+    #[cold]
+    pub fn stack_overflow(&self, message: String) -> ! {
+        let backtrace = self.backtrace(BACKTRACE_LIMIT);
+        raise(TypeId::StackOverflow, format!("{message}{backtrace}"))
+    }
+
     #[inline(always)]
     pub fn pop_stack(&mut self) -> Value {
         self.stack.pop().expect("Stack should not be empty")
@@ -78,6 +238,23 @@ impl Runtime {
         self.stack[len - 1 - n]
     }
 
+    /// The top `N` values, deepest first, without popping them.
+    #[inline(always)]
+    pub fn top_n<const N: usize>(&self) -> [Value; N] {
+        let len = self.stack.len();
+        if len < N {
+            panic!("Stack underflow: requested {N}, but stack size is {len}");
+        }
+        self.stack[len - N..].try_into().unwrap()
+    }
+
+    /// Pops the top `N` values and pushes `v` in their place.
+    #[inline(always)]
+    pub fn replace_top_n<const N: usize>(&mut self, v: Value) {
+        self.stack.truncate(self.stack.len() - N);
+        self.stack.push(v);
+    }
+
     #[inline(always)]
     pub fn pop_stack_n<const N: usize>(&mut self) -> [Value; N] {
         let mut array = [MaybeUninit::uninit(); N];
@@ -87,12 +264,144 @@ impl Runtime {
         unsafe { MaybeUninit::array_assume_init(array) }
     }
 
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            stack: self.stack.len(),
+            frames: self.frames.len(),
+            depth: self.depth,
+            handlers: self.exceptions.handlers.len(),
+            signals: self.exceptions.signals.len(),
+        }
+    }
+
+    /// Drops everything pushed onto the runtime's stacks since `checkpoint` was taken.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.stack.truncate(checkpoint.stack);
+        self.frames.truncate(checkpoint.frames);
+        self.depth = checkpoint.depth;
+        self.exceptions.handlers.truncate(checkpoint.handlers);
+        self.exceptions.signals.truncate(checkpoint.signals);
+        self.tail_call = None;
+    }
+
+    /// Defines a built-in type as a direct subtype of `Object`, bound to a global of the same
+    /// name.
     pub fn define_type(&mut self, id: TypeId) {
-        let map = HeapMap::new(&mut self.heap, 16);
-        self.globals.types.insert(
-            Value::from_uint(id as u64),
-            Value::from_ptr(map.ptr.as_ptr() as u64),
-        );
+        let superclass = (id != TypeId::Object).then_some(TypeId::Object);
+        self.create_type(id, id.builtin_name(), superclass);
+    }
+
+    /// Like `define_type`, but for a built-in type deeper in the hierarchy.
+    pub fn define_builtin_subtype(&mut self, id: TypeId, superclass: TypeId) {
+        self.create_type(id, id.builtin_name(), Some(superclass));
+    }
+
+    /// Defines a new type from Howl, answering its type object.
+    pub fn define_subtype(&mut self, name: &str, superclass: TypeId) -> Value {
+        let id = TypeId(self.globals.next_type_id);
+        self.globals.next_type_id += 1;
+        self.create_type(id, name, Some(superclass))
+    }
+
+    fn create_type(&mut self, id: TypeId, name: &str, superclass: Option<TypeId>) -> Value {
+        let handlers = HeapMap::new(&mut self.heap, 16);
+        let type_object = TypeObject {
+            id,
+            handlers: Value::from_ptr(handlers.ptr.as_ptr() as u64),
+            superclass: superclass.map_or(Value::nil(), |s| self.type_value(s)),
+            name: Value::from_str(name, &mut self.heap),
+        };
+        let ptr = self
+            .heap
+            .alloc(size_of::<TypeObject>() as u64, TypeId::Type);
+        unsafe { ptr.cast::<TypeObject>().write(type_object) };
+        let value = Value::from_ptr(ptr.as_ptr() as u64);
+
+        self.globals.types.insert(Value::from_uint(id.0), value);
+        self.define_global(name, value);
+        value
+    }
+
+    /// The type object for `id`, as seen from Howl.
+    pub fn type_value(&self, id: TypeId) -> Value {
+        self.globals
+            .types
+            .get(&Value::from_uint(id.0))
+            .unwrap_or_else(|| panic!("Type {} doesn't exist", id.0))
+    }
+
+    pub fn type_object(&self, id: TypeId) -> &'static TypeObject {
+        as_type_object(self.type_value(id))
+    }
+
+    pub fn type_name(&self, id: TypeId) -> &'static str {
+        as_string(self.type_object(id).name)
+    }
+
+    pub fn superclass_of(&self, id: TypeId) -> Option<TypeId> {
+        let superclass = self.type_object(id).superclass;
+        (!superclass.is_nil()).then(|| as_type_object(superclass).id)
+    }
+
+    /// One line per running handler, innermost first, e.g. `  in Dog(Animal)>>describe` for
+    /// a handler Dog inherited from Animal. Only the innermost `limit` are listed, followed
+    /// by how many were left out.
+    pub fn backtrace(&self, limit: usize) -> String {
+        let mut out = String::new();
+        for frame in self.frames.iter().rev().take(limit) {
+            let receiver = frame.receiver.type_of();
+            let selector = self.globals.idents.get(frame.selector).unwrap_or_default();
+            out.push_str("\n  in ");
+            out.push_str(self.type_name(receiver));
+            if receiver != frame.holder {
+                out.push_str(&format!("({})", self.type_name(frame.holder)));
+            }
+            out.push_str(&format!(">>{selector}"));
+        }
+        if let Some(rest) = self.frames.len().checked_sub(limit).filter(|&n| n > 0) {
+            out.push_str(&format!("\n  ... and {rest} more"));
+        }
+        out
+    }
+
+    /// Every type defined from Howl, in the order they were defined.
+    pub fn user_types(&self) -> impl Iterator<Item = TypeId> + use<> {
+        (TypeId::FIRST_USER.0..self.globals.next_type_id).map(TypeId)
+    }
+
+    /// Whether `id` is `ancestor` or one of its subtypes.
+    pub fn inherits_from(&self, id: TypeId, ancestor: TypeId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.superclass_of(id);
+        }
+        false
+    }
+
+    /// Finds the handler for `selector`, starting at `type_id` and walking up the superclass
+    /// chain. Also answers the type the handler was found in.
+    pub fn lookup_handler(&mut self, type_id: TypeId, selector: u64) -> Option<(Value, TypeId)> {
+        let mut current = Some(type_id);
+        while let Some(id) = current {
+            if let Some(handler) = self.handler_map(id).get(&Value::from_uint(selector)) {
+                return Some((handler, id));
+            }
+            current = self.superclass_of(id);
+        }
+        None
+    }
+
+    fn handler_map(&mut self, id: TypeId) -> HeapMap {
+        let handlers = self.type_object(id).handlers;
+        unsafe {
+            HeapMap::from_ptr(
+                NonNull::new(handlers.as_ptr() as *mut Value).unwrap(),
+                &mut self.heap,
+            )
+        }
     }
 
     pub fn register_handler(
@@ -101,35 +410,72 @@ impl Runtime {
         handler: ExternHandler,
         type_id: TypeId,
     ) {
-        let handler_map_id = self.globals.idents.add(name);
-        let handler_ptr = self
-            .globals
-            .types
-            .get(&Value::from_uint(type_id as u64))
-            .unwrap()
-            .as_ptr();
-        let mut handler_map = unsafe {
-            HeapMap::from_ptr(
-                NonNull::new(handler_ptr as *mut Value).unwrap(),
-                &mut self.heap,
-            )
-        };
-        handler_map.insert(
-            Value::from_uint(handler_map_id),
-            #[allow(clippy::fn_to_numeric_cast)]
-            Value::from_uint(handler as u64),
-        );
+        let selector = self.globals.idents.add(name);
+        #[allow(clippy::fn_to_numeric_cast)]
+        self.insert_handler(type_id, selector, Value::from_uint(handler as u64));
+    }
+
+    /// Installs a Block as the handler for `selector`; it receives the receiver as its first
+    /// argument.
+    pub fn register_block_handler(&mut self, selector: u64, block: Value, type_id: TypeId) {
+        self.insert_handler(type_id, selector, block);
+    }
+
+    fn insert_handler(&mut self, type_id: TypeId, selector: u64, handler: Value) {
+        self.globals.handler_epoch += 1;
+        if type_id == TypeId::Int && matches!(selector, IdentArena::ADD | IdentArena::LESS) {
+            self.globals.int_fast_path = false;
+        }
+        self.handler_map(type_id)
+            .insert(Value::from_uint(selector), handler);
+    }
+
+    /// Removes the handlers of `type_id` whose selector `keep` answers false for.
+    pub fn retain_handlers(&mut self, type_id: TypeId, mut keep: impl FnMut(&str) -> bool) {
+        self.globals.handler_epoch += 1;
+        let mut handlers = self.handler_map(type_id);
+        let idents = &self.globals.idents;
+        handlers.retain(|selector, _| {
+            idents
+                .get(selector.as_uint())
+                .is_some_and(|name| keep(&name))
+        });
+    }
+
+    /// Whether a type with id `id` has been defined.
+    pub fn has_type(&self, id: TypeId) -> bool {
+        self.globals.types.get(&Value::from_uint(id.0)).is_some()
+    }
+
+    /// Binds `value` to the global variable `name`.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        let id = self.globals.idents.add(name);
+        self.globals.vars.insert(Value::from_uint(id), value);
     }
 
     pub fn push_op(&mut self, op: OpCode) {
-        self.code.push(op);
+        self.code.emit(op);
     }
+
+    /// Sends `selector` to `receiver` from native code, answering the handler's result.
+    pub fn send(&mut self, receiver: Value, selector: &str, args: &[Value]) -> Option<Value> {
+        let id = self.globals.idents.add(selector);
+        self.push_stack(receiver);
+        self.stack.extend_from_slice(args);
+        send_message(self, id, args.len() as u64)
+    }
+}
+
+pub fn as_type_object(v: Value) -> &'static TypeObject {
+    unsafe { &*(v.as_ptr() as *const TypeObject) }
 }
 
 pub struct Heap {
     ptr: NonNull<u8>,
     cap: u64,
     cursor: u64,
+    /// Maps allocated here, whose tables are dropped with the heap
+    maps: Vec<NonNull<HeapMapHeader>>,
 }
 
 pub struct HeapAllocator<T> {
@@ -157,6 +503,17 @@ pub struct Allocation {
     data_ptr: NonNull<u8>,
 }
 
+impl Allocation {
+    /// Where the `DataHeader` the allocation was made with starts
+    pub fn header(&self) -> NonNull<u8> {
+        self.header_ptr
+    }
+
+    pub fn data(&self) -> NonNull<u8> {
+        self.data_ptr
+    }
+}
+
 impl Heap {
     const ALIGN: usize = 16;
 
@@ -171,10 +528,28 @@ impl Heap {
             ptr,
             cap,
             cursor: 0,
+            maps: Vec::new(),
         }
     }
 
-    pub fn alloc<DataHeader>(&mut self, layout: Layout, type_id: TypeId) -> Option<Allocation> {
+    /// Allocates `size` bytes for a Howl object of type `type_id`, which `Value::type_of` reads
+    /// back from the metadata just before them. Raises an `OutOfMemory` once the heap is full.
+    pub fn alloc(&mut self, size: u64, type_id: TypeId) -> NonNull<u8> {
+        Layout::from_size_align(size as usize, Self::ALIGN)
+            .ok()
+            .and_then(|layout| self.alloc_with_header::<()>(layout, type_id))
+            .map(|allocation| allocation.data_ptr)
+            .unwrap_or_else(|| {
+                let message = format!("Heap capacity of {} bytes exceeded", self.cap);
+                raise(TypeId::OutOfMemory, message)
+            })
+    }
+
+    pub fn alloc_with_header<DataHeader>(
+        &mut self,
+        layout: Layout,
+        type_id: TypeId,
+    ) -> Option<Allocation> {
         let metadata_size = size_of::<HeapMetadata>() as u64;
         let header_size = size_of::<DataHeader>() as u64;
         let reserved = metadata_size + header_size;
@@ -208,6 +583,10 @@ impl Heap {
         })
     }
 
+    pub fn track_map(&mut self, map: NonNull<HeapMapHeader>) {
+        self.maps.push(map);
+    }
+
     pub fn get_alloc_header<Header>(&self, ptr: NonNull<u8>) -> NonNull<Header> {
         unsafe { ptr.sub(size_of::<Header>()).cast() }
     }
@@ -223,7 +602,7 @@ impl Heap {
 unsafe impl<Header> Allocator for HeapAllocator<Header> {
     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
         unsafe { self.inner.as_ptr().as_mut_unchecked() }
-            .alloc::<Header>(layout, TypeId::NONE)
+            .alloc_with_header::<Header>(layout, TypeId::NONE)
             .map(|allocation| NonNull::slice_from_raw_parts(allocation.data_ptr, layout.size()))
             .ok_or(AllocError)
     }
@@ -240,6 +619,9 @@ impl Default for Heap {
 impl Drop for Heap {
     fn drop(&mut self) {
         unsafe {
+            for map in self.maps.drain(..) {
+                map.drop_in_place();
+            }
             dealloc(
                 self.ptr.as_ptr(),
                 Layout::from_size_align(self.cap as usize, Self::ALIGN).unwrap(),
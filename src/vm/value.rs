@@ -1,17 +1,22 @@
-use crate::{parser::Literal, vm::runtime::Heap};
+use crate::{
+    parser::Literal,
+    vm::{bigint::BigInt, runtime::Heap},
+};
 use std::{f64, ptr::NonNull};
 
 #[repr(transparent)]
-#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct Value(u64);
 
 const NAN_MASK: u64 = 0x7FF0_0000_0000_0000;
 const FLOAT_NAN: u64 = 0x7FF8_0000_0000_0000;
+const CHAR: u64 = 0xFFFB_0000_0000_0000;
 const PTR: u64 = 0xFFFC_0000_0000_0000;
 const INT: u64 = 0xFFFD_0000_0000_0000;
 const FALSE: u64 = 0xFFFE_0000_0000_0000;
 const TRUE: u64 = 0xFFFE_0000_0000_0001;
 const NIL: u64 = 0xFFFE_0000_0000_0002;
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
 const HIGH_MASK: u64 = 0xFFFF_0000_0000_0000;
 
 impl Value {
@@ -19,26 +24,52 @@ impl Value {
     pub fn from_literal(l: Literal, heap: &mut Heap) -> Self {
         match l {
             Literal::Int(i) => Self::from_int(i),
+            Literal::LargeInt(n) => Self::from_bigint(&n, heap),
             Literal::Float(f) => Self::from_float(f),
             Literal::Bool(b) => Self::from_bool(b),
+            Literal::Char(c) => Self::from_char(c),
             Literal::Nil => Self::nil(),
-            Literal::String(s) => {
-                let s_len = s.len() as u64;
-                let header_len = 16_u64;
-
-                let ptr = heap.alloc(s_len + header_len, TypeId::String).unwrap();
-                unsafe {
-                    ptr.cast::<u64>().write(s_len);
-                    ptr.add(header_len as usize).copy_from_nonoverlapping(
-                        NonNull::new(s.as_ptr() as *mut u8).unwrap(),
-                        s_len as usize,
-                    );
-                };
-
-                Self::from_ptr(ptr.as_ptr() as u64)
-            }
+            Literal::String(s) => Self::from_str(&s, heap),
         }
     }
+    pub fn from_str(s: &str, heap: &mut Heap) -> Self {
+        Self::alloc_str(s, TypeId::String, heap)
+    }
+    /// Symbols share the string layout and differ only in their type.
+    pub fn from_symbol(s: &str, heap: &mut Heap) -> Self {
+        Self::alloc_str(s, TypeId::Symbol, heap)
+    }
+    fn alloc_str(s: &str, type_id: TypeId, heap: &mut Heap) -> Self {
+        let s_len = s.len() as u64;
+        let header_len = 16_u64;
+
+        let ptr = heap.alloc(s_len + header_len, type_id);
+        unsafe {
+            ptr.cast::<u64>().write(s_len);
+            ptr.add(header_len as usize).copy_from_nonoverlapping(
+                NonNull::new(s.as_ptr() as *mut u8).unwrap(),
+                s_len as usize,
+            );
+        };
+
+        Self::from_ptr(ptr.as_ptr() as u64)
+    }
+    /// Lays out the limbs after a 16-byte header holding the limb count and the sign.
+    pub fn from_bigint(n: &BigInt, heap: &mut Heap) -> Self {
+        let limbs = n.limbs();
+        let header_len = 16_u64;
+
+        let ptr = heap.alloc(header_len + 4 * limbs.len() as u64, TypeId::LargeInt);
+        unsafe {
+            ptr.cast::<u64>().write(limbs.len() as u64);
+            ptr.cast::<u64>().add(1).write(n.is_negative() as u64);
+            ptr.add(header_len as usize)
+                .cast::<u32>()
+                .copy_from_nonoverlapping(NonNull::from(limbs).cast(), limbs.len());
+        };
+
+        Self::from_ptr(ptr.as_ptr() as u64)
+    }
     pub fn from_float(f: f64) -> Self {
         if f.is_nan() {
             Self(FLOAT_NAN)
@@ -48,13 +79,17 @@ impl Value {
     }
 
     pub fn from_int(i: i32) -> Self {
-        Self(INT | (i as u64))
+        Self(INT | (i as u32 as u64))
     }
 
     pub fn from_uint(i: u64) -> Self {
         Self(INT | i)
     }
 
+    pub fn from_char(c: char) -> Self {
+        Self(CHAR | c as u64)
+    }
+
     pub fn from_bool(b: bool) -> Self {
         Self(FALSE | (b as u64))
     }
@@ -69,7 +104,9 @@ impl Value {
 
     // IS_ METHODS
     pub fn is_float(&self) -> bool {
-        self.0 <= NAN_MASK
+        // Negative doubles carry the sign bit, so mask it off before comparing
+        // against infinity; tagged values all sit above that once masked
+        self.0 <= FLOAT_NAN || (self.0 & !SIGN_BIT) <= NAN_MASK
     }
 
     pub fn is_nan(&self) -> bool {
@@ -80,6 +117,10 @@ impl Value {
         (self.0 & HIGH_MASK) == INT
     }
 
+    pub fn is_char(&self) -> bool {
+        (self.0 & HIGH_MASK) == CHAR
+    }
+
     pub fn is_ptr(&self) -> bool {
         (self.0 & HIGH_MASK) == PTR
     }
@@ -109,6 +150,11 @@ impl Value {
         (self.0 & 0x0000_FFFF_FFFF_FFFF) as i32
     }
 
+    pub fn as_char(&self) -> char {
+        // Only ever constructed from a valid `char` in `from_char`
+        unsafe { char::from_u32_unchecked(self.0 as u32) }
+    }
+
     pub fn as_uint(&self) -> u64 {
         self.0 & 0x0000_FFFF_FFFF_FFFF
     }
@@ -124,6 +170,7 @@ impl Value {
                 (self.as_ptr() as *const u8).sub(16).cast::<TypeId>().read()
             },
             _ if self.is_int() => TypeId::Int,
+            _ if self.is_char() => TypeId::Character,
             _ if self.is_float() => TypeId::Float,
             _ if self.is_false() => TypeId::False,
             _ if self.is_true() => TypeId::True,
@@ -132,18 +179,85 @@ impl Value {
     }
 }
 
-#[repr(u64)]
-#[derive(Copy, Clone)]
-pub enum TypeId {
-    NONE,
+/// Identifies a type. The built-in types have fixed ids, while types defined from Howl are
+/// numbered upwards from `TypeId::FIRST_USER`.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct TypeId(pub u64);
+
+#[allow(non_upper_case_globals)]
+impl TypeId {
+    pub const NONE: TypeId = TypeId(0);
     // Primitives
-    Nil,
-    Int,
-    Float,
-    True,
-    False,
-
-    String,
-    HeapMap,
-    CompiledBytecode,
+    pub const Nil: TypeId = TypeId(1);
+    pub const Int: TypeId = TypeId(2);
+    pub const Float: TypeId = TypeId(3);
+    pub const Character: TypeId = TypeId(4);
+    pub const True: TypeId = TypeId(5);
+    pub const False: TypeId = TypeId(6);
+
+    pub const String: TypeId = TypeId(7);
+    pub const Symbol: TypeId = TypeId(8);
+    pub const Array: TypeId = TypeId(9);
+    pub const LargeInt: TypeId = TypeId(10);
+    pub const HeapMap: TypeId = TypeId(11);
+    pub const CompiledBytecode: TypeId = TypeId(12);
+    pub const Interval: TypeId = TypeId(13);
+    pub const Transcript: TypeId = TypeId(14);
+    /// The root of the type hierarchy
+    pub const Object: TypeId = TypeId(15);
+    /// The type of type objects, i.e. the values bound to `Object`, `Int`, ...
+    pub const Type: TypeId = TypeId(16);
+    /// The supertype of unit tests, see `std::test_case`
+    pub const TestCase: TypeId = TypeId(17);
+    /// The root of the error hierarchy, see `std::error`
+    pub const Error: TypeId = TypeId(18);
+    pub const MessageNotUnderstood: TypeId = TypeId(19);
+    pub const ZeroDivide: TypeId = TypeId(20);
+    pub const IndexOutOfBounds: TypeId = TypeId(21);
+    pub const ArgumentError: TypeId = TypeId(22);
+    /// Raised when running code goes past `Config::max_depth` or `Config::max_stack`
+    pub const StackOverflow: TypeId = TypeId(23);
+    /// Raised when the host sets the runtime's interrupt flag, see `vm::budget`
+    pub const Interrupted: TypeId = TypeId(24);
+    /// Raised when running code runs out of fuel or past its deadline
+    pub const BudgetExceeded: TypeId = TypeId(25);
+    /// Raised when an allocation doesn't fit in the heap, see `RuntimeBuilder::heap_capacity`
+    pub const OutOfMemory: TypeId = TypeId(26);
+
+    pub const FIRST_USER: TypeId = TypeId(64);
+
+    /// The name of a built-in type; user types keep theirs in their type object.
+    pub fn builtin_name(self) -> &'static str {
+        match self {
+            TypeId::NONE => "NONE",
+            TypeId::Nil => "Nil",
+            TypeId::Int => "Int",
+            TypeId::Float => "Float",
+            TypeId::Character => "Character",
+            TypeId::True => "True",
+            TypeId::False => "False",
+            TypeId::String => "String",
+            TypeId::Symbol => "Symbol",
+            TypeId::Array => "Array",
+            TypeId::LargeInt => "LargeInt",
+            TypeId::HeapMap => "HeapMap",
+            TypeId::CompiledBytecode => "Block",
+            TypeId::Interval => "Interval",
+            TypeId::Transcript => "Transcript",
+            TypeId::Object => "Object",
+            TypeId::Type => "Type",
+            TypeId::TestCase => "TestCase",
+            TypeId::Error => "Error",
+            TypeId::MessageNotUnderstood => "MessageNotUnderstood",
+            TypeId::ZeroDivide => "ZeroDivide",
+            TypeId::IndexOutOfBounds => "IndexOutOfBounds",
+            TypeId::ArgumentError => "ArgumentError",
+            TypeId::StackOverflow => "StackOverflow",
+            TypeId::Interrupted => "Interrupted",
+            TypeId::BudgetExceeded => "BudgetExceeded",
+            TypeId::OutOfMemory => "OutOfMemory",
+            TypeId(id) => panic!("Type {id} is not a built-in type"),
+        }
+    }
 }
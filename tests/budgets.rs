@@ -0,0 +1,89 @@
+//! Runs endless Howl loops under each kind of execution budget, checking that they stop with
+//! the right error and that the runtime still works once the limit is lifted.
+
+mod common;
+
+use common::{run, with_output};
+use howl::vm::{output::OutputBuffer, runtime::Runtime, value::TypeId};
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+const ENDLESS: &str = "n = 0; [ n = n + 1; ] loop;";
+
+fn runtime() -> (Runtime, OutputBuffer) {
+    with_output(Runtime::default())
+}
+
+fn assert_usable(rt: &mut Runtime, out: &OutputBuffer) {
+    out.clear();
+    assert_eq!(run(rt, "x = 1 + 2; x display;"), None);
+    assert_eq!(out.contents(), "3\n");
+}
+
+#[test]
+fn fuel() {
+    let (mut rt, out) = runtime();
+    rt.budget.set_fuel(Some(10_000));
+    let error = run(&mut rt, ENDLESS);
+    assert_eq!(
+        error,
+        Some((TypeId::BudgetExceeded, "Ran out of fuel".to_string()))
+    );
+    assert_eq!(rt.budget.fuel(), Some(0));
+
+    rt.budget.set_fuel(None);
+    assert_usable(&mut rt, &out);
+}
+
+#[test]
+fn fuel_cannot_be_caught_to_keep_running() {
+    let (mut rt, out) = runtime();
+    rt.budget.set_fuel(Some(10_000));
+    let source = "r = [ [ ] loop; ] on: BudgetExceeded do: [ e | \"caught\"; ]; r display;";
+    let error = run(&mut rt, source);
+    assert_eq!(
+        error.map(|(type_id, _)| type_id),
+        Some(TypeId::BudgetExceeded)
+    );
+    assert_eq!(out.contents(), "");
+}
+
+#[test]
+fn deadline() {
+    let (mut rt, out) = runtime();
+    rt.budget
+        .set_deadline(Some(Instant::now() + Duration::from_millis(50)));
+    let error = run(&mut rt, ENDLESS);
+    assert_eq!(
+        error,
+        Some((TypeId::BudgetExceeded, "Ran past its deadline".to_string()))
+    );
+
+    rt.budget.set_deadline(None);
+    assert_usable(&mut rt, &out);
+}
+
+#[test]
+fn interrupt() {
+    let (mut rt, out) = runtime();
+    let interrupt = rt.budget.interrupt_flag();
+    let setter = {
+        let interrupt = interrupt.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            interrupt.store(true, Ordering::Relaxed);
+        })
+    };
+    let error = run(&mut rt, ENDLESS);
+    setter.join().unwrap();
+    assert_eq!(
+        error,
+        Some((TypeId::Interrupted, "Interrupted".to_string()))
+    );
+
+    interrupt.store(false, Ordering::Relaxed);
+    assert_usable(&mut rt, &out);
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests that drive a `Runtime` from Rust.
+
+use howl::{
+    std::error::{RaisedError, UnhandledError},
+    vm::{output::OutputBuffer, runtime::Runtime, value::TypeId},
+};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Sends `rt`'s output to a buffer that the test can read back.
+pub fn with_output(mut rt: Runtime) -> (Runtime, OutputBuffer) {
+    let out = OutputBuffer::default();
+    rt.out = Box::new(out.clone());
+    (rt, out)
+}
+
+/// Runs `source`, answering the type and message of the error it raised, if any.
+pub fn run(rt: &mut Runtime, source: &str) -> Option<(TypeId, String)> {
+    let stmts = howl::parse(source, rt).unwrap();
+    let checkpoint = rt.checkpoint();
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| howl::run(stmts, rt))).err()?;
+    rt.restore(checkpoint);
+    // Errors that went through a handler on the way out are error objects by then
+    let type_id = match payload.downcast_ref::<RaisedError>() {
+        Some(raised) => raised.type_id,
+        None => {
+            let unhandled = payload.downcast_ref::<UnhandledError>();
+            unhandled.expect("Expected a Howl error").error.type_of()
+        }
+    };
+    Some((type_id, howl::panic_message(&*payload)))
+}
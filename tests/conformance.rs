@@ -0,0 +1,153 @@
+//! Runs every `tests/*.howl` program and compares what it prints with the `.out` file next to
+//! it. Programs that are expected to fail also have a `.err` file holding the diagnostic they
+//! fail with.
+//!
+//! Every program runs at each optimisation level, which must not change what it does.
+//!
+//! Run with `HOWL_BLESS=1` to write the current results to the `.out` and `.err` files instead
+//! of comparing against them.
+
+use howl::vm::{
+    optimizer::OptLevel,
+    output::OutputBuffer,
+    runtime::{Config, Runtime},
+};
+use std::{
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    thread,
+};
+
+const OPT_LEVELS: [(&str, OptLevel); 3] = [
+    ("-O0", OptLevel::None),
+    ("-O1", OptLevel::Peephole),
+    ("-O2", OptLevel::Fold),
+];
+
+struct Outcome {
+    out: String,
+    err: Option<String>,
+}
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let mut cases: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "howl"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "No .howl files in {}", dir.display());
+
+    let bless = env::var_os("HOWL_BLESS").is_some();
+    // Runtime errors are recorded as diagnostics rather than printed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let failures: Vec<String> = cases
+        .iter()
+        .flat_map(|case| {
+            if bless {
+                bless_case(case, &run_case_on_thread(case, OptLevel::None));
+                return Vec::new();
+            }
+            OPT_LEVELS
+                .iter()
+                .filter_map(|&(flag, opt_level)| {
+                    let failure = check_case(case, &run_case_on_thread(case, opt_level))?;
+                    Some(format!("{flag} {failure}"))
+                })
+                .collect()
+        })
+        .collect();
+    panic::set_hook(default_hook);
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} conformance runs failed:\n\n{}",
+            failures.len(),
+            cases.len() * OPT_LEVELS.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Debug builds take several KB of native stack per nested block, more than a test thread has
+/// for programs recursing up to `Config::max_depth`.
+fn run_case_on_thread(case: &Path, opt_level: OptLevel) -> Outcome {
+    let case = case.to_path_buf();
+    thread::Builder::new()
+        .stack_size(CASE_STACK_SIZE)
+        .spawn(move || run_case(&case, opt_level))
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+const CASE_STACK_SIZE: usize = 64 << 20;
+
+fn run_case(case: &Path, opt_level: OptLevel) -> Outcome {
+    let source = fs::read_to_string(case).unwrap();
+    let name = format!("tests/{}", case.file_name().unwrap().to_string_lossy());
+
+    let out = OutputBuffer::default();
+    let mut rt = Runtime {
+        out: Box::new(out.clone()),
+        err: Box::new(OutputBuffer::default()),
+        ..Runtime::new(Config {
+            opt_level,
+            ..Config::default()
+        })
+    };
+
+    let err = match howl::parse(&source, &mut rt) {
+        Ok(stmts) => panic::catch_unwind(AssertUnwindSafe(|| howl::run(stmts, &mut rt)))
+            .err()
+            .map(|payload| format!("error: {}\n", howl::panic_message(&*payload))),
+        Err(e) => Some(howl::parse_diagnostic(&source, &name, &e) + "\n"),
+    };
+    Outcome {
+        out: out.contents(),
+        err,
+    }
+}
+
+fn bless_case(case: &Path, outcome: &Outcome) {
+    fs::write(case.with_extension("out"), &outcome.out).unwrap();
+    let err_path = case.with_extension("err");
+    match &outcome.err {
+        Some(err) => fs::write(err_path, err).unwrap(),
+        None if err_path.exists() => fs::remove_file(err_path).unwrap(),
+        None => {}
+    }
+}
+
+/// Describes how `outcome` differs from what `case` expects, if it does.
+fn check_case(case: &Path, outcome: &Outcome) -> Option<String> {
+    let name = case.file_name().unwrap().to_string_lossy();
+    let Ok(expected_out) = fs::read_to_string(case.with_extension("out")) else {
+        return Some(format!(
+            "{name}: no .out file, run with HOWL_BLESS=1 to create one\n"
+        ));
+    };
+    let expected_err = fs::read_to_string(case.with_extension("err")).ok();
+
+    let mut problems = Vec::new();
+    if outcome.out != expected_out {
+        problems.push(format!(
+            "output differs\n--- expected\n{expected_out}--- actual\n{}",
+            outcome.out
+        ));
+    }
+    match (&expected_err, &outcome.err) {
+        (Some(expected), Some(actual)) if expected != actual => problems.push(format!(
+            "error differs\n--- expected\n{expected}--- actual\n{actual}"
+        )),
+        (Some(expected), None) => problems.push(format!("expected it to fail with\n{expected}")),
+        (None, Some(actual)) => problems.push(format!("failed unexpectedly with\n{actual}")),
+        _ => {}
+    }
+
+    (!problems.is_empty()).then(|| format!("{name}: {}", problems.join("\n")))
+}
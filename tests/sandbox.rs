@@ -0,0 +1,109 @@
+//! Runs Howl in runtimes built with only some std handlers, checking that the rest can't be
+//! reached and that the sandbox combines with the other limits.
+
+mod common;
+
+use common::{run, with_output};
+use howl::vm::{builder::RuntimeBuilder, value::TypeId};
+
+fn sandbox() -> RuntimeBuilder {
+    RuntimeBuilder::sandboxed()
+        .allow_type(TypeId::Int)
+        .allow_type(TypeId::CompiledBytecode)
+        .allow_handlers(
+            TypeId::Object,
+            &["==", "printString", "displayString", "display"],
+        )
+        .allow_handlers(TypeId::String, &["size", ","])
+}
+
+#[test]
+fn allowed_handlers_run() {
+    let (mut rt, out) = with_output(sandbox().build());
+    let error = run(
+        &mut rt,
+        "x = 40 + 2; x display; s = \"abc\"; n = s size; n display;",
+    );
+    assert_eq!(error, None);
+    assert_eq!(out.contents(), "42\n3\n");
+}
+
+#[test]
+fn other_handlers_are_not_understood() {
+    let (mut rt, out) = with_output(sandbox().build());
+    let cases = [
+        (
+            "Transcript show: \"hi\";",
+            "Transcript does not understand #show:",
+        ),
+        (
+            "s = \"abc\"; t = s reverse;",
+            "String does not understand #reverse",
+        ),
+        ("x = 1.5 + 1;", "Float does not understand #+"),
+        ("T = Type named: \"T\";", "Type does not understand #named:"),
+    ];
+    for (source, message) in cases {
+        let error = run(&mut rt, source);
+        assert_eq!(
+            error,
+            Some((TypeId::MessageNotUnderstood, message.to_string()))
+        );
+    }
+    assert_eq!(out.contents(), "");
+}
+
+#[test]
+fn int_arithmetic_left_out_is_not_understood() {
+    let (mut rt, _) = with_output(
+        RuntimeBuilder::sandboxed()
+            .allow_handlers(TypeId::Int, &["-"])
+            .build(),
+    );
+    assert_eq!(run(&mut rt, "x = 3 - 1;"), None);
+    let error = run(&mut rt, "x = 1 + 2;");
+    assert_eq!(
+        error,
+        Some((
+            TypeId::MessageNotUnderstood,
+            "Int does not understand #+".to_string()
+        ))
+    );
+}
+
+#[test]
+fn combines_with_fuel() {
+    let (mut rt, _) = with_output(sandbox().fuel(10_000).build());
+    let error = run(&mut rt, "n = 0; [ n = n + 1; ] loop;");
+    assert_eq!(
+        error,
+        Some((TypeId::BudgetExceeded, "Ran out of fuel".to_string()))
+    );
+}
+
+#[test]
+fn combines_with_heap_capacity() {
+    let (mut rt, _) = with_output(sandbox().heap_capacity(256 << 10).build());
+    let error = run(&mut rt, "s = \"heap\"; [ s = s , s; ] loop;");
+    assert_eq!(
+        error,
+        Some((
+            TypeId::OutOfMemory,
+            "Heap capacity of 262144 bytes exceeded".to_string()
+        ))
+    );
+
+    // Code that doesn't allocate still runs
+    assert_eq!(run(&mut rt, "x = 1 + 2;"), None);
+}
+
+#[test]
+fn everything_is_allowed_by_default() {
+    let (mut rt, out) = with_output(RuntimeBuilder::new().build());
+    let error = run(
+        &mut rt,
+        "Transcript show: \"hi\"; s = \"abc\"; t = s reverse; t display;",
+    );
+    assert_eq!(error, None);
+    assert_eq!(out.contents(), "hicba\n");
+}